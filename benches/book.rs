@@ -0,0 +1,144 @@
+//! Parsing of a 150 level book, the biggest and most frequent payload.
+//!
+//! Run with `cargo bench --bench book`. Each group compares a copy of the parsing of
+//! the previous release with the one used by the client.
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use crypto_com_exchange::{Offer, SubscribeResult};
+use serde::de::{self, SeqAccess, Visitor};
+use serde::{Deserialize, Deserializer};
+
+const LEVELS: usize = 150;
+
+/// A book frame like the exchange sends for `book.BTC_USDT.150`
+fn book_result() -> String {
+    let levels = |start: f64, step: f64| {
+        (0..LEVELS)
+            .map(|i| {
+                let price = start + step * i as f64;
+                let quantity = 0.0123 + (i % 17) as f64 * 0.5;
+                format!("[\"{price:.2}\",\"{quantity:.4}\",\"{}\"]", i % 9 + 1)
+            })
+            .collect::<Vec<_>>()
+            .join(",")
+    };
+    format!(
+        "{{\"channel\":\"book\",\"instrument_name\":\"BTC_USDT\",\"subscription\":\"book.BTC_USDT.150\",\"depth\":150,\"data\":[{{\"bids\":[{}],\"asks\":[{}],\"t\":1654780033786,\"tt\":1654780033755,\"u\":1654780033786}}]}}",
+        levels(30082.5, -0.5),
+        levels(30083.0, 0.5),
+    )
+}
+
+/// The offer of the previous release, with its visitor copied as it was: every
+/// element is read into an owned `String`, then parsed
+#[derive(Debug)]
+#[allow(dead_code)]
+struct BaselineOffer {
+    price: f64,
+    quantity: f64,
+    amount: f64,
+}
+
+struct BaselineOfferVisitor;
+
+impl<'de> Visitor<'de> for BaselineOfferVisitor {
+    type Value = BaselineOffer;
+
+    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            formatter,
+            "a sequence of numbers as strings (price, quantity, amount)"
+        )
+    }
+
+    fn visit_seq<M>(self, mut seq: M) -> Result<Self::Value, M::Error>
+    where
+        M: SeqAccess<'de>,
+    {
+        let price_str: String = seq
+            .next_element()?
+            .ok_or_else(|| serde::de::Error::custom("Missing price"))?;
+        let quantity_str: String = seq
+            .next_element()?
+            .ok_or_else(|| serde::de::Error::custom("Missing quantity"))?;
+        let amount_str: String = seq
+            .next_element()?
+            .ok_or_else(|| serde::de::Error::custom("Missing amount"))?;
+
+        let price = price_str.parse::<f64>().map_err(de::Error::custom)?;
+        let quantity = quantity_str.parse::<f64>().map_err(de::Error::custom)?;
+        let amount = amount_str.parse::<f64>().map_err(de::Error::custom)?;
+
+        Ok(BaselineOffer {
+            price,
+            quantity,
+            amount,
+        })
+    }
+}
+
+impl<'de> Deserialize<'de> for BaselineOffer {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_seq(BaselineOfferVisitor)
+    }
+}
+
+/// The book result of the previous release, read through the tagged enum
+#[derive(Deserialize, Debug)]
+#[serde(tag = "channel")]
+#[allow(dead_code)]
+enum BaselineResult {
+    #[serde(rename = "book")]
+    BookResult {
+        instrument_name: String,
+        subscription: String,
+        depth: i64,
+        data: Vec<BaselineBook>,
+    },
+}
+
+#[derive(Deserialize, Debug)]
+#[allow(dead_code)]
+struct BaselineBook {
+    bids: Vec<BaselineOffer>,
+    asks: Vec<BaselineOffer>,
+    #[serde(rename = "t")]
+    time: u64,
+}
+
+fn offers(c: &mut Criterion) {
+    let result = book_result();
+    let value: serde_json::Value = serde_json::from_str(&result).unwrap();
+    let bids = value["data"][0]["bids"].to_string();
+
+    let mut group = c.benchmark_group("offers");
+    group.bench_function("baseline", |b| {
+        b.iter(|| serde_json::from_str::<Vec<BaselineOffer>>(black_box(&bids)).unwrap())
+    });
+    group.bench_function("borrowed", |b| {
+        b.iter(|| serde_json::from_str::<Vec<Offer>>(black_box(&bids)).unwrap())
+    });
+    group.finish();
+}
+
+fn subscribe_result(c: &mut Criterion) {
+    let result = book_result();
+
+    let mut group = c.benchmark_group("book_150");
+    group.bench_function("baseline", |b| {
+        b.iter(|| serde_json::from_str::<BaselineResult>(black_box(&result)).unwrap())
+    });
+    group.bench_function("tagged_enum", |b| {
+        b.iter(|| serde_json::from_str::<SubscribeResult>(black_box(&result)).unwrap())
+    });
+    group.bench_function("from_json", |b| {
+        b.iter(|| SubscribeResult::from_json(black_box(&result)).unwrap())
+    });
+    group.finish();
+}
+
+criterion_group!(benches, offers, subscribe_result);
+criterion_main!(benches);
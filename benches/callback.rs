@@ -0,0 +1,57 @@
+//! Cost of handing the results to the callback, without the parsing.
+//!
+//! Run with `cargo bench --bench callback`. The callback used to be behind a
+//! `tokio::sync::Mutex` locked for every result, it is now called directly.
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use futures::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use tokio::sync::Mutex;
+
+const RESULTS: u64 = 1000;
+
+type Locked<Fut> = Arc<Mutex<dyn Fn(u64) -> Fut + Send + Sync>>;
+type Direct<Fut> = Arc<dyn Fn(u64) -> Fut + Send + Sync>;
+
+fn callback(total: Arc<AtomicU64>) -> impl Fn(u64) -> std::future::Ready<()> + Send + Sync {
+    move |value| {
+        total.fetch_add(value, Ordering::Relaxed);
+        std::future::ready(())
+    }
+}
+
+async fn deliver_locked<Fut: Future<Output = ()>>(events: &Locked<Fut>) {
+    for value in 0..RESULTS {
+        let e = events.lock().await;
+        e(black_box(value)).await;
+    }
+}
+
+async fn deliver_direct<Fut: Future<Output = ()>>(events: &Direct<Fut>) {
+    for value in 0..RESULTS {
+        events(black_box(value)).await;
+    }
+}
+
+fn deliveries(c: &mut Criterion) {
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .build()
+        .unwrap();
+    let total = Arc::new(AtomicU64::new(0));
+
+    let mut group = c.benchmark_group("deliver_1000");
+    let locked: Locked<_> = Arc::new(Mutex::new(callback(total.clone())));
+    group.bench_function("mutex", |b| {
+        b.iter(|| runtime.block_on(deliver_locked(&locked)))
+    });
+    let direct: Direct<_> = Arc::new(callback(total.clone()));
+    group.bench_function("direct", |b| {
+        b.iter(|| runtime.block_on(deliver_direct(&direct)))
+    });
+    group.finish();
+    black_box(total.load(Ordering::Relaxed));
+}
+
+criterion_group!(benches, deliveries);
+criterion_main!(benches);
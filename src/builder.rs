@@ -0,0 +1,628 @@
+use futures::future::Future;
+use std::fmt;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio_tungstenite::tungstenite::http::Uri;
+use tokio_tungstenite::tungstenite::protocol::WebSocketConfig;
+use tokio_util::sync::CancellationToken;
+
+use crate::client::{CryptoClient, CryptoError, EventType, HeartbeatMode, ParseFailurePolicy};
+use crate::message::Envelope;
+use crate::SubscribeResult;
+
+/// How long to wait between reconnection attempts
+#[derive(Debug, Clone, PartialEq)]
+pub struct BackoffPolicy {
+    /// Delay before the first attempt
+    pub initial: Duration,
+
+    /// Upper bound of the delay
+    pub max: Duration,
+
+    /// Factor applied to the delay after every failed attempt
+    pub multiplier: f64,
+
+    /// Give up after this many failed attempts, never if `None`
+    pub max_attempts: Option<u32>,
+}
+
+impl Default for BackoffPolicy {
+    fn default() -> Self {
+        BackoffPolicy {
+            initial: Duration::from_secs(1),
+            max: Duration::from_secs(60),
+            multiplier: 2.0,
+            max_attempts: None,
+        }
+    }
+}
+
+impl BackoffPolicy {
+    /// Default policy while the exchange is under maintenance, from 30 seconds up to 5 minutes
+    pub fn maintenance() -> Self {
+        BackoffPolicy {
+            initial: Duration::from_secs(30),
+            max: Duration::from_secs(300),
+            multiplier: 2.0,
+            max_attempts: None,
+        }
+    }
+
+    /// Delay before the attempt number `attempt`, starting at 1
+    pub fn delay(&self, attempt: u32) -> Duration {
+        let exponent = attempt.saturating_sub(1).min(i32::MAX as u32) as i32;
+        let secs = self.initial.as_secs_f64() * self.multiplier.powi(exponent);
+        Duration::from_secs_f64(secs.min(self.max.as_secs_f64()))
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if self.initial.is_zero() {
+            return Err("the initial backoff delay cannot be zero".to_owned());
+        }
+        if self.max < self.initial {
+            return Err("the max backoff delay cannot be lower than the initial one".to_owned());
+        }
+        if !self.multiplier.is_finite() || self.multiplier < 1.0 {
+            return Err(format!(
+                "the backoff multiplier has to be at least 1, got {}",
+                self.multiplier
+            ));
+        }
+        if self.max_attempts == Some(0) {
+            return Err("the max reconnection attempts cannot be zero".to_owned());
+        }
+        Ok(())
+    }
+}
+
+/// At most `requests` requests are sent in any window of `per`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RateLimit {
+    pub requests: u32,
+    pub per: Duration,
+}
+
+impl RateLimit {
+    pub fn new(requests: u32, per: Duration) -> RateLimit {
+        RateLimit { requests, per }
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if self.requests == 0 {
+            return Err("the rate limit has to allow at least one request".to_owned());
+        }
+        if self.per.is_zero() {
+            return Err("the rate limit window cannot be zero".to_owned());
+        }
+        Ok(())
+    }
+}
+
+/// Api key used to authenticate the user connection
+#[derive(Clone)]
+pub(crate) struct Credentials {
+    pub(crate) api_key: String,
+    pub(crate) api_secret: String,
+}
+
+/// The secret is never printed
+impl fmt::Debug for Credentials {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Credentials")
+            .field("api_key", &self.api_key)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Everything configurable of a client, see `CryptoClientBuilder`
+#[derive(Debug, Clone)]
+pub(crate) struct ClientConfig {
+    pub(crate) market_url: String,
+    pub(crate) user_url: String,
+    pub(crate) connect_timeout: Option<Duration>,
+    pub(crate) heartbeat_timeout: Option<Duration>,
+    pub(crate) ping_interval: Option<Duration>,
+    pub(crate) backoff: Option<BackoffPolicy>,
+    pub(crate) maintenance_backoff: BackoffPolicy,
+    pub(crate) rate_limit: Option<RateLimit>,
+    pub(crate) websocket_config: Option<WebSocketConfig>,
+    pub(crate) credentials: Option<Credentials>,
+    pub(crate) request_timeout: Duration,
+    pub(crate) heartbeat_mode: HeartbeatMode,
+    pub(crate) disconnect_timeout: Duration,
+    pub(crate) cancellation_token: Option<CancellationToken>,
+    pub(crate) ack_timeout: Duration,
+    pub(crate) parse_failure_policy: ParseFailurePolicy,
+    pub(crate) allow_unknown_channels: bool,
+    pub(crate) max_reauth_attempts: u32,
+    pub(crate) raw_passthrough: bool,
+}
+
+impl Default for ClientConfig {
+    fn default() -> Self {
+        ClientConfig {
+            market_url: "wss://stream.crypto.com/v2/market".to_string(),
+            user_url: "wss://stream.crypto.com/v2/user".to_string(),
+            connect_timeout: None,
+            heartbeat_timeout: None,
+            ping_interval: None,
+            backoff: None,
+            maintenance_backoff: BackoffPolicy::maintenance(),
+            rate_limit: None,
+            websocket_config: None,
+            credentials: None,
+            request_timeout: Duration::from_secs(10),
+            heartbeat_mode: HeartbeatMode::default(),
+            disconnect_timeout: Duration::from_secs(1),
+            cancellation_token: None,
+            ack_timeout: Duration::from_secs(10),
+            parse_failure_policy: ParseFailurePolicy::default(),
+            allow_unknown_channels: false,
+            max_reauth_attempts: 3,
+            raw_passthrough: false,
+        }
+    }
+}
+
+fn validate_url(name: &str, url: &str) -> Result<(), String> {
+    let uri = url
+        .parse::<Uri>()
+        .map_err(|error| format!("the {name} url \"{url}\" is malformed: {error}"))?;
+    if !matches!(uri.scheme_str(), Some("ws") | Some("wss")) {
+        return Err(format!(
+            "the {name} url \"{url}\" has to start with ws:// or wss://"
+        ));
+    }
+    if uri.host().is_none_or(str::is_empty) {
+        return Err(format!("the {name} url \"{url}\" has no host"));
+    }
+    Ok(())
+}
+
+fn validate_duration(name: &str, duration: Option<Duration>) -> Result<(), String> {
+    match duration {
+        Some(duration) if duration.is_zero() => Err(format!("the {name} cannot be zero")),
+        _ => Ok(()),
+    }
+}
+
+impl ClientConfig {
+    fn validate(&self) -> Result<(), String> {
+        validate_url("market", &self.market_url)?;
+        validate_url("user", &self.user_url)?;
+        validate_duration("connect timeout", self.connect_timeout)?;
+        validate_duration("heartbeat timeout", self.heartbeat_timeout)?;
+        validate_duration("ping interval", self.ping_interval)?;
+        validate_duration("request timeout", Some(self.request_timeout))?;
+        validate_duration("disconnect timeout", Some(self.disconnect_timeout))?;
+        validate_duration("ack timeout", Some(self.ack_timeout))?;
+        if self.parse_failure_policy == ParseFailurePolicy::DisconnectAfter(0) {
+            return Err("the parse failures before disconnecting cannot be zero".to_owned());
+        }
+        if self.max_reauth_attempts == 0 {
+            return Err("the max authentication attempts cannot be zero".to_owned());
+        }
+        if let Some(backoff) = &self.backoff {
+            backoff.validate()?;
+        }
+        self.maintenance_backoff.validate()?;
+        if let Some(rate_limit) = &self.rate_limit {
+            rate_limit.validate()?;
+        }
+        if let Some(config) = &self.websocket_config {
+            if config.max_message_size == Some(0) {
+                return Err("the max websocket message size cannot be zero".to_owned());
+            }
+            if config.max_frame_size == Some(0) {
+                return Err("the max websocket frame size cannot be zero".to_owned());
+            }
+            if config.max_write_buffer_size <= config.write_buffer_size {
+                return Err(
+                    "the max websocket write buffer size has to be greater than the write buffer size"
+                        .to_owned(),
+                );
+            }
+        }
+        if let Some(credentials) = &self.credentials {
+            if credentials.api_key.is_empty() || credentials.api_secret.is_empty() {
+                return Err("the api key and secret cannot be empty".to_owned());
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Configures and creates a `CryptoClient`.
+///
+/// Nothing is checked until `build`, which fails with `CryptoError::ConfigError`
+/// describing the first invalid option.
+pub struct CryptoClientBuilder<Fut: Future<Output = ()> + Send + Sync + 'static, T> {
+    events: EventType<T, Fut>,
+    container: T,
+    config: ClientConfig,
+}
+
+impl<Fut: Future<Output = ()> + Send + Sync + 'static, T: Send + 'static>
+    CryptoClientBuilder<Fut, T>
+where
+    T: Clone,
+{
+    /// Builder of a client calling `f` with every result and a clone of `container`
+    pub fn new(
+        f: impl Fn(Result<SubscribeResult, CryptoError>, T) -> Fut + Send + Sync + 'static,
+        container: T,
+    ) -> CryptoClientBuilder<Fut, T> {
+        CryptoClientBuilder::new_with_envelope(
+            move |envelope: Envelope, container: T| f(envelope.result, container),
+            container,
+        )
+    }
+
+    /// Same as `new` but the callback receives every result inside an `Envelope`
+    pub fn new_with_envelope(
+        f: impl Fn(Envelope, T) -> Fut + Send + Sync + 'static,
+        container: T,
+    ) -> CryptoClientBuilder<Fut, T> {
+        CryptoClientBuilder {
+            events: Arc::new(f),
+            container,
+            config: ClientConfig::default(),
+        }
+    }
+
+    /// Value cloned into every call of the callback
+    pub fn with_container(mut self, container: T) -> Self {
+        self.container = container;
+        self
+    }
+
+    /// Url used by `connect_market`
+    pub fn with_market_url(mut self, url: impl Into<String>) -> Self {
+        self.config.market_url = url.into();
+        self
+    }
+
+    /// Url used by `connect_user`
+    pub fn with_user_url(mut self, url: impl Into<String>) -> Self {
+        self.config.user_url = url.into();
+        self
+    }
+
+    /// Maximum time to open the websocket, unlimited by default
+    pub fn with_connect_timeout(mut self, timeout: Duration) -> Self {
+        self.config.connect_timeout = Some(timeout);
+        self
+    }
+
+    /// The connection is considered dead when nothing is received in `timeout`.
+    /// The exchange sends a heartbeat every 30 seconds. Disabled by default.
+    pub fn with_heartbeat_timeout(mut self, timeout: Duration) -> Self {
+        self.config.heartbeat_timeout = Some(timeout);
+        self
+    }
+
+    /// Send a websocket ping every `interval`, disabled by default
+    pub fn with_ping_interval(mut self, interval: Duration) -> Self {
+        self.config.ping_interval = Some(interval);
+        self
+    }
+
+    /// Reconnect with `policy` when the connection is lost, restoring the subscriptions.
+    /// Disabled by default.
+    pub fn with_backoff_policy(mut self, policy: BackoffPolicy) -> Self {
+        self.config.backoff = Some(policy);
+        self
+    }
+
+    /// Reconnect with `policy` instead while the exchange is under maintenance, only if
+    /// there is a backoff policy. `BackoffPolicy::maintenance` by default.
+    pub fn with_maintenance_backoff(mut self, policy: BackoffPolicy) -> Self {
+        self.config.maintenance_backoff = policy;
+        self
+    }
+
+    /// Delay the requests sent to the exchange to respect `limit`, unlimited by default
+    pub fn with_rate_limit(mut self, limit: RateLimit) -> Self {
+        self.config.rate_limit = Some(limit);
+        self
+    }
+
+    /// Low level websocket options, the tungstenite defaults otherwise
+    pub fn with_websocket_config(mut self, config: WebSocketConfig) -> Self {
+        self.config.websocket_config = Some(config);
+        self
+    }
+
+    /// Api key used to authenticate right after `connect_user` and after every reconnection
+    pub fn with_credentials(
+        mut self,
+        api_key: impl Into<String>,
+        api_secret: impl Into<String>,
+    ) -> Self {
+        self.config.credentials = Some(Credentials {
+            api_key: api_key.into(),
+            api_secret: api_secret.into(),
+        });
+        self
+    }
+
+    /// Maximum time to wait for the response of a request like `get_instruments`
+    pub fn with_request_timeout(mut self, timeout: Duration) -> Self {
+        self.config.request_timeout = timeout;
+        self
+    }
+
+    /// How the heartbeats of the exchange are answered, `HeartbeatMode::Auto` by default
+    pub fn with_heartbeat_mode(mut self, mode: HeartbeatMode) -> Self {
+        self.config.heartbeat_mode = mode;
+        self
+    }
+
+    /// Maximum time `disconnect` waits for the unsubscribe acks, and then for the
+    /// reader to finish. 1 second by default.
+    pub fn with_disconnect_timeout(mut self, timeout: Duration) -> Self {
+        self.config.disconnect_timeout = timeout;
+        self
+    }
+
+    /// `CryptoError::AckTimeout` is delivered when a subscribe request is not acknowledged
+    /// in `timeout`, 10 seconds by default
+    pub fn with_ack_timeout(mut self, timeout: Duration) -> Self {
+        self.config.ack_timeout = timeout;
+        self
+    }
+
+    /// What to do with the frames that cannot be parsed, `ParseFailurePolicy::Continue` by default.
+    /// When the policy closes the connection the reader ends with `CryptoError::ParseFailures`,
+    /// or reconnects if there is a backoff policy.
+    pub fn with_parse_failure_policy(mut self, policy: ParseFailurePolicy) -> Self {
+        self.config.parse_failure_policy = policy;
+        self
+    }
+
+    /// `subscribe` rejects the channels it does not know with `CryptoError::InvalidChannel`
+    /// unless `allow` is true, for the channels newer than this crate. The known channels
+    /// are checked anyway.
+    pub fn allow_unknown_channels(mut self, allow: bool) -> Self {
+        self.config.allow_unknown_channels = allow;
+        self
+    }
+
+    /// When the exchange answers UNAUTHORIZED on the user connection the client authenticates
+    /// again with the credentials, up to `attempts` times in a row. The reader then ends with
+    /// `CryptoError::AuthenticationFailed`. 3 by default.
+    pub fn with_max_reauth_attempts(mut self, attempts: u32) -> Self {
+        self.config.max_reauth_attempts = attempts;
+        self
+    }
+
+    /// Keep the text of every frame in `Envelope::raw`, also when it cannot be parsed.
+    /// Off by default, the text is not copied then.
+    pub fn with_raw_passthrough(mut self, enabled: bool) -> Self {
+        self.config.raw_passthrough = enabled;
+        self
+    }
+
+    /// Close the connection and stop the reader, with `Ok(())`, when `token` is cancelled
+    pub fn with_cancellation_token(mut self, token: CancellationToken) -> Self {
+        self.config.cancellation_token = Some(token);
+        self
+    }
+
+    /// Check the configuration and create the client
+    pub fn build(self) -> Result<CryptoClient<Fut, T>, CryptoError> {
+        self.config.validate().map_err(CryptoError::ConfigError)?;
+        Ok(CryptoClient::from_parts(
+            self.events,
+            self.container,
+            self.config,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn builder() -> CryptoClientBuilder<impl Future<Output = ()> + Send + Sync + 'static, ()> {
+        CryptoClientBuilder::new(
+            |_: Result<SubscribeResult, CryptoError>, _: ()| async {},
+            (),
+        )
+    }
+
+    fn build_error<Fut: Future<Output = ()> + Send + Sync + 'static>(
+        builder: CryptoClientBuilder<Fut, ()>,
+    ) -> String {
+        match builder.build() {
+            Err(CryptoError::ConfigError(message)) => message,
+            Err(other) => panic!("unexpected {other:?}"),
+            Ok(_) => panic!("the configuration should be invalid"),
+        }
+    }
+
+    #[test]
+    fn defaults_match_the_client() {
+        let client = builder().build().unwrap();
+        let config = client.config();
+        assert_eq!(config.market_url, "wss://stream.crypto.com/v2/market");
+        assert_eq!(config.user_url, "wss://stream.crypto.com/v2/user");
+        assert_eq!(config.connect_timeout, None);
+        assert_eq!(config.heartbeat_timeout, None);
+        assert_eq!(config.ping_interval, None);
+        assert_eq!(config.backoff, None);
+        assert_eq!(config.maintenance_backoff, BackoffPolicy::maintenance());
+        assert_eq!(config.rate_limit, None);
+        assert!(config.websocket_config.is_none());
+        assert!(config.credentials.is_none());
+        assert_eq!(config.request_timeout, Duration::from_secs(10));
+        assert_eq!(config.heartbeat_mode, HeartbeatMode::Auto);
+        assert_eq!(config.disconnect_timeout, Duration::from_secs(1));
+        assert!(config.cancellation_token.is_none());
+        assert_eq!(config.ack_timeout, Duration::from_secs(10));
+        assert_eq!(config.parse_failure_policy, ParseFailurePolicy::Continue);
+        assert!(!config.allow_unknown_channels);
+        assert_eq!(config.max_reauth_attempts, 3);
+        assert!(!config.raw_passthrough);
+
+        let plain = CryptoClient::new(
+            |_: Result<SubscribeResult, CryptoError>, _: ()| async {},
+            (),
+        );
+        assert_eq!(format!("{:?}", plain.config()), format!("{config:?}"));
+    }
+
+    #[test]
+    fn setters_are_kept() {
+        let client = builder()
+            .with_market_url("ws://localhost:1234/market")
+            .with_user_url("wss://localhost/user".to_owned())
+            .with_connect_timeout(Duration::from_secs(3))
+            .with_heartbeat_timeout(Duration::from_secs(45))
+            .with_ping_interval(Duration::from_secs(15))
+            .with_backoff_policy(BackoffPolicy::default())
+            .with_maintenance_backoff(BackoffPolicy::default())
+            .with_rate_limit(RateLimit::new(100, Duration::from_secs(1)))
+            .with_websocket_config(WebSocketConfig::default())
+            .with_credentials("key", "secret")
+            .with_request_timeout(Duration::from_secs(2))
+            .with_heartbeat_mode(HeartbeatMode::Manual)
+            .with_disconnect_timeout(Duration::from_millis(500))
+            .with_ack_timeout(Duration::from_secs(5))
+            .with_parse_failure_policy(ParseFailurePolicy::DisconnectAfter(3))
+            .allow_unknown_channels(true)
+            .with_max_reauth_attempts(5)
+            .with_raw_passthrough(true)
+            .build()
+            .unwrap();
+        let config = client.config();
+        assert_eq!(config.market_url, "ws://localhost:1234/market");
+        assert_eq!(config.user_url, "wss://localhost/user");
+        assert_eq!(config.connect_timeout, Some(Duration::from_secs(3)));
+        assert_eq!(config.heartbeat_timeout, Some(Duration::from_secs(45)));
+        assert_eq!(config.ping_interval, Some(Duration::from_secs(15)));
+        assert_eq!(config.backoff, Some(BackoffPolicy::default()));
+        assert_eq!(config.maintenance_backoff, BackoffPolicy::default());
+        assert_eq!(
+            config.rate_limit,
+            Some(RateLimit::new(100, Duration::from_secs(1)))
+        );
+        assert!(config.websocket_config.is_some());
+        assert_eq!(config.request_timeout, Duration::from_secs(2));
+        assert_eq!(config.heartbeat_mode, HeartbeatMode::Manual);
+        assert_eq!(config.disconnect_timeout, Duration::from_millis(500));
+        assert_eq!(config.ack_timeout, Duration::from_secs(5));
+        assert_eq!(
+            config.parse_failure_policy,
+            ParseFailurePolicy::DisconnectAfter(3)
+        );
+        assert!(config.allow_unknown_channels);
+        assert_eq!(config.max_reauth_attempts, 5);
+        assert!(config.raw_passthrough);
+
+        // The secret is not printed
+        let credentials = format!("{:?}", config.credentials);
+        assert!(credentials.contains("key"));
+        assert!(!credentials.contains("secret"));
+    }
+
+    #[test]
+    fn invalid_urls_are_rejected() {
+        let error = build_error(builder().with_market_url("not a url"));
+        assert!(error.contains("market url"), "{error}");
+
+        let error = build_error(builder().with_user_url("https://stream.crypto.com/v2/user"));
+        assert!(error.contains("ws://"), "{error}");
+
+        let error = build_error(builder().with_market_url("wss:///v2/market"));
+        assert!(error.contains("market url"), "{error}");
+    }
+
+    #[test]
+    fn zero_values_are_rejected() {
+        let error =
+            build_error(builder().with_rate_limit(RateLimit::new(0, Duration::from_secs(1))));
+        assert!(error.contains("at least one request"), "{error}");
+
+        let error = build_error(builder().with_rate_limit(RateLimit::new(10, Duration::ZERO)));
+        assert!(error.contains("window"), "{error}");
+
+        let error = build_error(builder().with_connect_timeout(Duration::ZERO));
+        assert!(error.contains("connect timeout"), "{error}");
+
+        let error = build_error(builder().with_ping_interval(Duration::ZERO));
+        assert!(error.contains("ping interval"), "{error}");
+
+        let error = build_error(builder().with_heartbeat_timeout(Duration::ZERO));
+        assert!(error.contains("heartbeat timeout"), "{error}");
+
+        let error = build_error(
+            builder().with_parse_failure_policy(ParseFailurePolicy::DisconnectAfter(0)),
+        );
+        assert!(error.contains("parse failures"), "{error}");
+
+        let error = build_error(builder().with_max_reauth_attempts(0));
+        assert!(error.contains("authentication attempts"), "{error}");
+
+        let error = build_error(builder().with_credentials("", "secret"));
+        assert!(error.contains("api key"), "{error}");
+
+        let websocket_config = WebSocketConfig {
+            max_message_size: Some(0),
+            ..Default::default()
+        };
+        let error = build_error(builder().with_websocket_config(websocket_config));
+        assert!(error.contains("message size"), "{error}");
+
+        let websocket_config = WebSocketConfig {
+            write_buffer_size: 1024,
+            max_write_buffer_size: 1024,
+            ..Default::default()
+        };
+        let error = build_error(builder().with_websocket_config(websocket_config));
+        assert!(error.contains("write buffer"), "{error}");
+    }
+
+    #[test]
+    fn invalid_backoff_is_rejected() {
+        let error = build_error(builder().with_backoff_policy(BackoffPolicy {
+            multiplier: 0.5,
+            ..Default::default()
+        }));
+        assert!(error.contains("multiplier"), "{error}");
+
+        let error = build_error(builder().with_backoff_policy(BackoffPolicy {
+            initial: Duration::from_secs(10),
+            max: Duration::from_secs(1),
+            ..Default::default()
+        }));
+        assert!(error.contains("max backoff"), "{error}");
+
+        let error = build_error(builder().with_backoff_policy(BackoffPolicy {
+            max_attempts: Some(0),
+            ..Default::default()
+        }));
+        assert!(error.contains("attempts"), "{error}");
+
+        let error = build_error(builder().with_maintenance_backoff(BackoffPolicy {
+            initial: Duration::ZERO,
+            ..BackoffPolicy::maintenance()
+        }));
+        assert!(error.contains("initial backoff"), "{error}");
+    }
+
+    #[test]
+    fn backoff_delay_grows_up_to_the_max() {
+        let policy = BackoffPolicy {
+            initial: Duration::from_millis(100),
+            max: Duration::from_secs(1),
+            multiplier: 2.0,
+            max_attempts: None,
+        };
+        assert_eq!(policy.delay(1), Duration::from_millis(100));
+        assert_eq!(policy.delay(2), Duration::from_millis(200));
+        assert_eq!(policy.delay(4), Duration::from_millis(800));
+        assert_eq!(policy.delay(5), Duration::from_secs(1));
+        assert_eq!(policy.delay(u32::MAX), Duration::from_secs(1));
+    }
+}
@@ -1,21 +1,30 @@
 use futures::future::Future;
-use futures::stream::SplitSink;
+use futures::stream::{SplitSink, SplitStream};
 use futures::{SinkExt, StreamExt};
 use hmac::{Hmac, Mac};
-use log::{debug, error, info};
+use log::{debug, info};
 use serde_json::Value;
 use sha2::Sha256;
+use std::collections::{BTreeSet, HashMap, HashSet, VecDeque};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::sync::Arc;
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use thiserror::Error;
 use tokio::net::TcpStream;
-use tokio::sync::Mutex;
+use tokio::sync::mpsc::{unbounded_channel, WeakUnboundedSender};
+use tokio::sync::{oneshot, Mutex};
 use tokio::task::JoinHandle;
 use tokio_tungstenite::tungstenite::protocol::{CloseFrame, Message};
-use tokio_tungstenite::{connect_async, MaybeTlsStream, WebSocketStream};
+use tokio_tungstenite::{connect_async_with_config, MaybeTlsStream, WebSocketStream};
 
+use crate::builder::{ClientConfig, RateLimit};
+use crate::health::{ConnectionState, Health, HealthTracker};
+use crate::message;
+use crate::message::{Envelope, Response};
+use crate::model::{self, Balance, Book, Channel, InstrumentInfo, OrderPage};
+use crate::reader::{spawn_ack_timer, Reader, UNAUTHORIZED_CODES};
+use crate::router::{self, Router};
 use crate::subscription;
-use crate::{message, SubscribeResult};
 
 type HmacSha256 = Hmac<Sha256>;
 
@@ -24,8 +33,9 @@ pub enum CryptoError {
     #[error("Cannot join to a task")]
     JoinError(#[from] tokio::task::JoinError),
 
+    /// Boxed, the error of tungstenite is much bigger than the other variants
     #[error("Tungstenite error")]
-    TungsteniteError(#[from] tokio_tungstenite::tungstenite::Error),
+    TungsteniteError(#[from] Box<tokio_tungstenite::tungstenite::Error>),
 
     #[error("Tungstenite error")]
     TungsteniteErrorString(String),
@@ -50,34 +60,483 @@ pub enum CryptoError {
     #[error("Not connected")]
     NotConnectedError,
 
+    #[error("Not authenticated")]
+    NotAuthenticatedError,
+
     #[error("Invalid sha length")]
     ShaInvalidLength(#[from] hmac::digest::InvalidLength),
+
+    #[error("Error \"{}\" ({code}) in {method} request (msgid:{id})", message.as_ref().unwrap_or(&"unknown".to_owned()))]
+    RequestError {
+        id: u64,
+        method: String,
+        code: u64,
+        message: Option<String>,
+    },
+
+    #[error("No response to request {id} in time")]
+    RequestTimeout { id: u64 },
+
+    /// The frame answering request `id` has a `method` of another request
+    #[error("Unexpected {method} response to request {id}")]
+    UnexpectedResponse { id: u64, method: String },
+
+    #[error("Invalid configuration: {0}")]
+    ConfigError(String),
+
+    #[error("Cannot connect in {timeout:?}")]
+    ConnectTimeout { timeout: Duration },
+
+    #[error("Nothing received in {timeout:?}")]
+    HeartbeatTimeout { timeout: Duration },
+
+    #[error("Cannot reconnect after {attempts} attempts")]
+    ReconnectFailed { attempts: u32 },
+
+    /// The exchange closed the connection or announced a maintenance. `resume_hint` is the
+    /// text of the exchange, it may say when the service is back
+    #[error("The exchange is under maintenance{}", resume_hint.as_ref().map(|hint| format!(": {hint}")).unwrap_or_default())]
+    Maintenance { resume_hint: Option<String> },
+
+    #[error("Subscribe request {id} to {channels:?} not acknowledged in time")]
+    AckTimeout { id: u64, channels: Vec<String> },
+
+    #[error("{failures} consecutive frames could not be parsed")]
+    ParseFailures { failures: u32 },
+
+    #[error("Invalid channel {channel}: {reason}")]
+    InvalidChannel { channel: String, reason: String },
+
+    #[error("Cannot authenticate again after {attempts} attempts")]
+    AuthenticationFailed { attempts: u32 },
+}
+
+impl From<tokio_tungstenite::tungstenite::Error> for CryptoError {
+    fn from(error: tokio_tungstenite::tungstenite::Error) -> Self {
+        CryptoError::TungsteniteError(Box::new(error))
+    }
+}
+
+pub(crate) type EventType<T, Fut> = Arc<dyn Fn(Envelope, T) -> Fut + Send + Sync>;
+/// How the heartbeats of the exchange are answered
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum HeartbeatMode {
+    /// The client answers every heartbeat
+    #[default]
+    Auto,
+    /// Heartbeats are delivered to the callback as `SubscribeResult::HeartbeatRequest`,
+    /// answer them with `respond_heartbeat`
+    Manual,
+    /// Heartbeats are ignored, the exchange will close the connection
+    /// unless someone else answers them
+    Disabled,
+}
+
+/// What the reader does with frames it cannot parse.
+/// The parse error is always delivered to the callback first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ParseFailurePolicy {
+    /// Skip the frame and keep reading
+    #[default]
+    Continue,
+    /// Close the connection on the first failure
+    Disconnect,
+    /// Close the connection after this many consecutive failures,
+    /// any frame parsed successfully resets the count
+    DisconnectAfter(u32),
+}
+
+impl ParseFailurePolicy {
+    /// Consecutive failures that close the connection, `None` if it is never closed
+    pub(crate) fn limit(self) -> Option<u32> {
+        match self {
+            ParseFailurePolicy::Continue => None,
+            ParseFailurePolicy::Disconnect => Some(1),
+            ParseFailurePolicy::DisconnectAfter(failures) => Some(failures),
+        }
+    }
+}
+
+type PendingType = Mutex<HashMap<u64, oneshot::Sender<Result<Response, CryptoError>>>>;
+type Sink = SplitSink<WebSocketStream<MaybeTlsStream<TcpStream>>, Message>;
+pub(crate) type Stream = SplitStream<WebSocketStream<MaybeTlsStream<TcpStream>>>;
+
+/// Delays the requests to stay under a `RateLimit`
+struct RateLimiter {
+    limit: RateLimit,
+    /// When the requests of the current window were sent
+    sent: VecDeque<tokio::time::Instant>,
+}
+
+impl RateLimiter {
+    fn new(limit: RateLimit) -> RateLimiter {
+        RateLimiter {
+            limit,
+            sent: VecDeque::new(),
+        }
+    }
+
+    /// Wait until one more request fits in the window
+    async fn acquire(&mut self) {
+        let now = tokio::time::Instant::now();
+        while self
+            .sent
+            .front()
+            .is_some_and(|sent| now.duration_since(*sent) >= self.limit.per)
+        {
+            self.sent.pop_front();
+        }
+        if self.sent.len() >= self.limit.requests as usize {
+            if let Some(oldest) = self.sent.pop_front() {
+                debug!("Rate limit reached, waiting");
+                tokio::time::sleep_until(oldest + self.limit.per).await;
+            }
+        }
+        self.sent.push_back(tokio::time::Instant::now());
+    }
+}
+
+/// State shared by the client and its reader task
+pub(crate) struct Shared {
+    /// Write half of the current connection, replaced when reconnecting
+    pub(crate) writer: Mutex<Option<Sink>>,
+    /// Requests waiting for their response, by message id
+    pub(crate) pending: PendingType,
+    /// Set by the reader when the exchange accepts the auth request
+    pub(crate) authenticated: AtomicBool,
+    /// Number of heartbeats received from the exchange
+    pub(crate) heartbeats: AtomicU64,
+    message_id: AtomicU64,
+    /// Channels subscribed, restored after a reconnection
+    pub(crate) subscriptions: Mutex<BTreeSet<String>>,
+    /// Set by `disconnect` so the reader does not reconnect
+    pub(crate) closing: AtomicBool,
+    limiter: Option<Mutex<RateLimiter>>,
+    acks: Mutex<Acks>,
+    pub(crate) health: HealthTracker,
+    /// Handlers of `on_channel`, kept across connections
+    pub(crate) router: Router,
+}
+
+/// Subscribe requests waiting for their ack
+#[derive(Default)]
+struct Acks {
+    /// Requests not settled yet, by message id
+    waiting: HashMap<u64, Waiting>,
+    /// Requests whose ack did not arrive in time
+    expired: HashSet<u64>,
+}
+
+/// A subscribe request whose channels are not all accepted or rejected yet
+struct Waiting {
+    /// Timer delivering `AckTimeout`
+    timer: JoinHandle<()>,
+    channels: Vec<String>,
+    accepted: Vec<String>,
+    rejected: Vec<(String, u64)>,
+}
+
+impl Waiting {
+    fn is_settled(&self, channel: &str) -> bool {
+        self.accepted.iter().any(|accepted| accepted == channel)
+            || self
+                .rejected
+                .iter()
+                .any(|(rejected, _)| rejected == channel)
+    }
+
+    fn settle(&mut self, channel: String, code: u64) {
+        if code == 0 {
+            self.accepted.push(channel);
+        } else {
+            self.rejected.push((channel, code));
+        }
+    }
+
+    fn unsettled(&self) -> Vec<String> {
+        self.channels
+            .iter()
+            .filter(|channel| !self.is_settled(channel))
+            .cloned()
+            .collect()
+    }
+}
+
+/// What a response settled of its subscribe request
+#[derive(Debug)]
+pub(crate) enum Settled {
+    /// Other channels of the request are waiting for their ack
+    Pending,
+    /// Every channel of the request is accepted or rejected
+    Done {
+        channels: usize,
+        accepted: Vec<String>,
+        rejected: Vec<(String, u64)>,
+    },
+    /// The ack arrived after the timeout
+    Late,
+    /// Not a request waiting for its ack
+    Unknown,
+}
+
+/// The channels of an expired subscribe request
+pub(crate) struct Expired {
+    pub(crate) unsettled: Vec<String>,
+    pub(crate) accepted: Vec<String>,
+    pub(crate) rejected: Vec<(String, u64)>,
+}
+
+impl Shared {
+    fn new(config: &ClientConfig) -> Shared {
+        Shared {
+            writer: Mutex::new(None),
+            pending: Mutex::new(HashMap::new()),
+            authenticated: AtomicBool::new(false),
+            heartbeats: AtomicU64::new(0),
+            message_id: AtomicU64::new(1),
+            subscriptions: Mutex::new(BTreeSet::new()),
+            closing: AtomicBool::new(false),
+            limiter: config
+                .rate_limit
+                .map(|limit| Mutex::new(RateLimiter::new(limit))),
+            acks: Mutex::new(Acks::default()),
+            health: HealthTracker::default(),
+            router: Router::default(),
+        }
+    }
+
+    /// Keep the timer of the subscribe request `id` until its channels are settled
+    pub(crate) async fn wait_ack(&self, id: u64, channels: Vec<String>, timer: JoinHandle<()>) {
+        self.acks.lock().await.waiting.insert(
+            id,
+            Waiting {
+                timer,
+                channels,
+                accepted: Vec::new(),
+                rejected: Vec::new(),
+            },
+        );
+    }
+
+    /// Stop waiting for the ack of `id`, the request was not sent
+    pub(crate) async fn cancel_ack(&self, id: u64) {
+        if let Some(waiting) = self.acks.lock().await.waiting.remove(&id) {
+            waiting.timer.abort();
+        }
+    }
+
+    /// Settle the channels of the request `id` answered with `code`. A response naming
+    /// one of the channels settles that channel only, the others settle every channel
+    /// left. The timer stops once every channel is settled.
+    pub(crate) async fn acknowledge(&self, id: u64, channel: Option<&str>, code: u64) -> Settled {
+        let mut acks = self.acks.lock().await;
+        let Some(waiting) = acks.waiting.get_mut(&id) else {
+            return match acks.expired.remove(&id) {
+                true => Settled::Late,
+                false => Settled::Unknown,
+            };
+        };
+        match channel.filter(|channel| waiting.channels.iter().any(|name| name == channel)) {
+            Some(channel) if !waiting.is_settled(channel) => {
+                waiting.settle(channel.to_owned(), code)
+            }
+            Some(_) => {}
+            None => {
+                for channel in waiting.unsettled() {
+                    waiting.settle(channel, code);
+                }
+            }
+        }
+        if waiting.accepted.len() + waiting.rejected.len() < waiting.channels.len() {
+            return Settled::Pending;
+        }
+        let waiting = acks.waiting.remove(&id).expect("the request is waiting");
+        waiting.timer.abort();
+        Settled::Done {
+            channels: waiting.channels.len(),
+            accepted: waiting.accepted,
+            rejected: waiting.rejected,
+        }
+    }
+
+    /// Called by the timer of `id`, None if the request is already settled
+    pub(crate) async fn expire_ack(&self, id: u64) -> Option<Expired> {
+        let mut acks = self.acks.lock().await;
+        let waiting = acks.waiting.remove(&id)?;
+        acks.expired.insert(id);
+        Some(Expired {
+            unsettled: waiting.unsettled(),
+            accepted: waiting.accepted,
+            rejected: waiting.rejected,
+        })
+    }
+
+    /// Remove the channels rejected by the exchange from the registry, except the ones
+    /// rejected because the session expired, they are subscribed again once authenticated
+    pub(crate) async fn forget_rejected(&self, rejected: &[(String, u64)]) {
+        let mut subscriptions = self.subscriptions.lock().await;
+        for (channel, code) in rejected {
+            if !UNAUTHORIZED_CODES.contains(code) {
+                subscriptions.remove(channel);
+            }
+        }
+    }
+
+    /// Stop every timer, the connection is going away
+    pub(crate) async fn forget_acks(&self) {
+        let mut acks = self.acks.lock().await;
+        for (_, waiting) in acks.waiting.drain() {
+            waiting.timer.abort();
+        }
+        acks.expired.clear();
+    }
+
+    pub(crate) fn next_id(&self) -> u64 {
+        self.message_id.fetch_add(1, Ordering::SeqCst)
+    }
+
+    async fn is_connected(&self) -> bool {
+        self.writer.lock().await.is_some()
+    }
+
+    pub(crate) async fn send(&self, message: Message) -> Result<(), CryptoError> {
+        match self.writer.lock().await.as_mut() {
+            Some(writer) => Ok(writer.send(message).await?),
+            None => Err(CryptoError::NotConnectedError),
+        }
+    }
+
+    /// Send a request right away, for heartbeats and the requests of the reader task
+    /// (auth and subscribing again), which must not sleep waiting for the rate limit
+    pub(crate) async fn send_request(
+        &self,
+        request: &subscription::Request,
+    ) -> Result<(), CryptoError> {
+        let text = serde_json::to_string(request)?;
+        self.send(Message::text(text)).await
+    }
+
+    /// Send a request once the rate limit allows it
+    pub(crate) async fn send_limited(
+        &self,
+        request: &subscription::Request,
+    ) -> Result<(), CryptoError> {
+        if let Some(limiter) = &self.limiter {
+            if !self.is_connected().await {
+                return Err(CryptoError::NotConnectedError);
+            }
+            limiter.lock().await.acquire().await;
+        }
+        self.send_request(request).await
+    }
+}
+
+/// Error for a response with the method of another request than `id`
+fn unexpected(id: u64, response: &Response) -> CryptoError {
+    CryptoError::UnexpectedResponse {
+        id,
+        method: response.method().to_owned(),
+    }
 }
 
-type EventType<T, Fut> =
-    Arc<Mutex<dyn Fn(Result<message::SubscribeResult, CryptoError>, T) -> Fut + Send + Sync>>;
-type WriterType =
-    Option<Arc<Mutex<SplitSink<WebSocketStream<MaybeTlsStream<TcpStream>>, Message>>>>;
+/// Channels of the params of a subscribe request, which need a `channels` array
+/// of known channels unless `allow_unknown`
+fn checked_channels(params: &Value, allow_unknown: bool) -> Result<Vec<String>, CryptoError> {
+    let Some(Value::Array(channels)) = params.get("channels") else {
+        return Err(CryptoError::InvalidChannel {
+            channel: params.to_string(),
+            reason: "the params need a `channels` array".to_owned(),
+        });
+    };
+    channels
+        .iter()
+        .map(|channel| {
+            let Some(name) = channel.as_str() else {
+                return Err(CryptoError::InvalidChannel {
+                    channel: channel.to_string(),
+                    reason: "the channels have to be strings".to_owned(),
+                });
+            };
+            model::channel::check(name, allow_unknown).map_err(|reason| {
+                CryptoError::InvalidChannel {
+                    channel: name.to_owned(),
+                    reason,
+                }
+            })?;
+            Ok(name.to_owned())
+        })
+        .collect()
+}
 
 pub struct CryptoClient<Fut: Future<Output = ()> + Send + Sync + 'static, T> {
     //events: Arc<Mutex<dyn Fn(Result<message::SubscribeResult>, std::sync::Arc<flume::Sender<T>>)-> Fut + Send + Sync>>,
     events: EventType<T, Fut>,
     reader_join: Option<JoinHandle<Result<(), CryptoError>>>,
-    writer: WriterType,
-    message_id: u64,
     //sender: std::sync::Arc<flume::Sender<T>>
     container: T,
-    market_url: String,
-    user_url: String,
+    config: ClientConfig,
+    shared: Arc<Shared>,
+    /// Queue of the dispatcher of the current reader, for the ack timers. Weak so the
+    /// dispatcher still ends with the reader
+    deliveries: Option<WeakUnboundedSender<Envelope>>,
 }
 
-fn nonce() -> u128 {
+pub(crate) fn nonce() -> u128 {
     match SystemTime::now().duration_since(UNIX_EPOCH) {
         Ok(n) => n.as_millis(),
         Err(_) => 0,
     }
 }
 
+/// Signed `public/auth` request with message id `id`
+pub(crate) fn auth_request(
+    id: u64,
+    api_key: &str,
+    api_secret: &str,
+) -> Result<subscription::Request, hmac::digest::InvalidLength> {
+    let n = nonce();
+    let message_to_sig = [
+        "public/auth".into(),
+        id.to_string(),
+        api_key.to_owned(),
+        n.to_string(),
+    ]
+    .concat();
+    let mut mac = HmacSha256::new_from_slice(api_secret.as_bytes())?;
+    mac.update(message_to_sig.as_bytes());
+    let result = mac.finalize();
+    let f = result.into_bytes();
+
+    Ok(subscription::Request::Auth {
+        id,
+        api_key: api_key.to_owned(),
+        sig: hex::encode(f),
+        nonce: n,
+    })
+}
+
+/// Abort the reader, its result if it had already finished
+async fn abort(reader: JoinHandle<Result<(), CryptoError>>) -> Result<(), CryptoError> {
+    reader.abort();
+    match reader.await {
+        Ok(result) => result,
+        Err(error) if error.is_cancelled() => Ok(()),
+        Err(error) => Err(error.into()),
+    }
+}
+
+/// Open a websocket to `uri` with the options of `config`
+pub(crate) async fn open(uri: &str, config: &ClientConfig) -> Result<(Sink, Stream), CryptoError> {
+    let connection = connect_async_with_config(uri, config.websocket_config, false);
+    let (ws_stream, _) = match config.connect_timeout {
+        Some(timeout) => tokio::time::timeout(timeout, connection)
+            .await
+            .map_err(|_| CryptoError::ConnectTimeout { timeout })??,
+        None => connection.await?,
+    };
+    Ok(ws_stream.split())
+}
+
 impl<Fut: Future<Output = ()> + Send + Sync + 'static, T: Send + 'static> CryptoClient<Fut, T>
 where
     T: Clone,
@@ -86,305 +545,2693 @@ where
     pub fn new(
         f: impl Fn(Result<message::SubscribeResult, CryptoError>, T) -> Fut + Send + Sync + 'static,
         container: T,
+    ) -> CryptoClient<Fut, T> {
+        CryptoClient::new_with_envelope(
+            move |envelope: Envelope, container: T| f(envelope.result, container),
+            container,
+        )
+    }
+
+    /// Same as `new` but the callback receives every result inside an `Envelope`
+    /// carrying the time its frame was read from the socket
+    pub fn new_with_envelope(
+        f: impl Fn(Envelope, T) -> Fut + Send + Sync + 'static,
+        container: T,
+    ) -> CryptoClient<Fut, T> {
+        CryptoClient::from_parts(Arc::new(f), container, ClientConfig::default())
+    }
+
+    /// Client with an already validated configuration, see `CryptoClientBuilder::build`
+    pub(crate) fn from_parts(
+        events: EventType<T, Fut>,
+        container: T,
+        config: ClientConfig,
     ) -> CryptoClient<Fut, T> {
         CryptoClient {
-            events: Arc::new(Mutex::new(f)),
+            events,
             reader_join: None,
-            writer: None,
-            message_id: 1,
             container,
-            market_url: "wss://stream.crypto.com/v2/market".to_string(),
-            user_url: "wss://stream.crypto.com/v2/user".to_string(),
+            shared: Arc::new(Shared::new(&config)),
+            config,
+            deliveries: None,
         }
     }
 
+    #[cfg(test)]
+    pub(crate) fn config(&self) -> &ClientConfig {
+        &self.config
+    }
+
+    #[deprecated(note = "use `CryptoClientBuilder::with_market_url`")]
     pub fn with_market_url(mut self, url: String) -> Self {
-        self.market_url = url;
+        self.config.market_url = url;
         self
     }
 
+    #[deprecated(note = "use `CryptoClientBuilder::with_user_url`")]
     pub fn with_user_url(mut self, url: String) -> Self {
-        self.user_url = url;
+        self.config.user_url = url;
+        self
+    }
+
+    /// Maximum time to wait for the response of a request like `get_instruments`
+    pub fn with_request_timeout(mut self, timeout: Duration) -> Self {
+        self.config.request_timeout = timeout;
         self
     }
 
+    /// How the heartbeats of the exchange are answered, `HeartbeatMode::Auto` by default
+    pub fn with_heartbeat_mode(mut self, mode: HeartbeatMode) -> Self {
+        self.config.heartbeat_mode = mode;
+        self
+    }
+
+    /// Number of heartbeats received from the exchange since the client was created
+    pub fn heartbeat_count(&self) -> u64 {
+        self.shared.heartbeats.load(Ordering::SeqCst)
+    }
+
+    /// True once the exchange accepted the auth request of the current connection
+    pub fn is_authenticated(&self) -> bool {
+        self.shared.authenticated.load(Ordering::SeqCst)
+    }
+
+    /// Snapshot of the state of the connection, cheap enough for a readiness probe
+    pub fn health(&self) -> Health {
+        self.shared.health.snapshot()
+    }
+
+    /// Deliver the results of `channel` to `handler` instead of the callback.
+    /// Handlers can be registered before or after connecting and are kept across
+    /// reconnections, the errors always go to the callback.
+    pub fn on_channel<F, HandlerFut>(&self, channel: Channel, handler: F)
+    where
+        F: Fn(Envelope) -> HandlerFut + Send + Sync + 'static,
+        HandlerFut: Future<Output = ()> + Send + 'static,
+    {
+        self.shared
+            .router
+            .add_channel(channel, router::handler(handler));
+    }
+
+    /// Deliver the results of the channels starting with `prefix`, like `"user."`, to `handler`.
+    /// A handler registered with `on_channel` wins, then the one of the longest prefix.
+    pub fn on_channel_prefix<F, HandlerFut>(&self, prefix: impl Into<String>, handler: F)
+    where
+        F: Fn(Envelope) -> HandlerFut + Send + Sync + 'static,
+        HandlerFut: Future<Output = ()> + Send + 'static,
+    {
+        self.shared
+            .router
+            .add_prefix(prefix.into(), router::handler(handler));
+    }
+
+    /// Send the results of `channel` to the callback again, false if it had no handler
+    pub fn off_channel(&self, channel: &Channel) -> bool {
+        self.shared.router.remove_channel(channel)
+    }
+
+    /// Remove the handler of `prefix`, false if it had none
+    pub fn off_channel_prefix(&self, prefix: &str) -> bool {
+        self.shared.router.remove_prefix(prefix)
+    }
+
+    /// Wait for the reader to finish and return its result, `Ok(())` after a
+    /// cooperative shutdown through the cancellation token
     pub async fn wait(&mut self) -> Result<(), CryptoError> {
-        if let Some(join) = self.reader_join.as_mut() {
-            if join.is_finished() {
-                Ok(())
-            } else {
-                join.await?
-            }
-        } else {
-            Ok(())
+        match self.reader_join.take() {
+            Some(join) => join.await?,
+            None => Ok(()),
         }
     }
 
+    /// Unsubscribe from every channel, close the connection and wait for the reader.
+    ///
+    /// Returns the final result of the reader, so an error it was carrying before the
+    /// shutdown is not lost. The reader is aborted if it does not finish in time.
     pub async fn disconnect(&mut self) -> Result<(), CryptoError> {
         info!("Disconnecting");
-        if let Some(writer) = self.writer.as_mut() {
-            debug!("Closing connection");
-            writer.lock().await.close().await?;
-            debug!("Connection closed");
+        let channels: Vec<String> = std::mem::take(&mut *self.shared.subscriptions.lock().await)
+            .into_iter()
+            .collect();
+        if !channels.is_empty() && self.shared.is_connected().await {
+            debug!("Unsubscribing from {} channels", channels.len());
+            let timeout = self.config.disconnect_timeout;
+            let unsubscribe = self
+                .request_with_timeout(
+                    |id| subscription::Request::Unsubscribe {
+                        id,
+                        params: subscription::UnsubscribeParams { channels },
+                        nonce: nonce(),
+                    },
+                    timeout,
+                )
+                .await;
+            if let Err(error) = unsubscribe {
+                debug!("Unsubscription not acknowledged: {error}");
+            }
         }
 
-        if let Some(reader) = self.reader_join.as_mut() {
-            debug!("Closing reader");
-            reader.abort();
-            reader.await.ok();
-            debug!("Reader closed");
-        }
+        self.close().await;
+
+        let result = match self.reader_join.take() {
+            Some(mut reader) => {
+                debug!("Waiting for the reader");
+                match tokio::time::timeout(self.config.disconnect_timeout, &mut reader).await {
+                    Ok(result) => result?,
+                    Err(_) => {
+                        debug!("Reader did not finish in time, aborting it");
+                        abort(reader).await
+                    }
+                }
+            }
+            None => Ok(()),
+        };
         info!("Disconnected");
-        Ok(())
+        result
+    }
+
+    /// Close the connection and abort the reader right away, without unsubscribing.
+    ///
+    /// Returns the result of the reader if it had already finished.
+    pub async fn disconnect_fast(&mut self) -> Result<(), CryptoError> {
+        info!("Disconnecting");
+        self.shared.subscriptions.lock().await.clear();
+        self.close().await;
+
+        let result = match self.reader_join.take() {
+            Some(reader) => {
+                debug!("Closing reader");
+                abort(reader).await
+            }
+            None => Ok(()),
+        };
+        info!("Disconnected");
+        result
+    }
+
+    /// Send the close frame, the reader finishes when the exchange answers it.
+    /// A connection the exchange already dropped cannot take it, the reader is
+    /// still waited for
+    async fn close(&mut self) {
+        self.shared.closing.store(true, Ordering::SeqCst);
+        self.shared.health.set_state(ConnectionState::Disconnected);
+        self.shared.forget_acks().await;
+        if let Some(mut writer) = self.shared.writer.lock().await.take() {
+            debug!("Closing connection");
+            match writer.close().await {
+                Ok(()) => debug!("Connection closed"),
+                Err(error) => debug!("Cannot send the close frame: {error}"),
+            }
+        }
     }
 
     pub async fn connect_market(&mut self) -> Result<(), CryptoError> {
-        let market_url = self.market_url.clone();
+        let market_url = self.config.market_url.clone();
         self.connect(&market_url).await?;
         Ok(())
     }
 
+    /// Connect to the user api, authenticating right away if the client has credentials
     pub async fn connect_user(&mut self) -> Result<(), CryptoError> {
-        let user_url = self.user_url.clone();
+        let user_url = self.config.user_url.clone();
 
         self.connect(&user_url).await?;
+        if let Some(credentials) = self.config.credentials.clone() {
+            self.auth(&credentials.api_key, &credentials.api_secret)
+                .await?;
+        }
         Ok(())
     }
 
     pub async fn connect(&mut self, uri: &str) -> Result<(), CryptoError> {
         info!("Connecting");
-        let connection = connect_async(uri).await?;
-        let (ws_stream, _) = connection;
-
-        let (write, mut read) = ws_stream.split();
-        let writer = Arc::new(Mutex::new(write));
-        let inner_writer = writer.clone();
-
-        let events = Arc::clone(&self.events);
-
-        //let cosa = self.sender.clone();
-        let cosa = self.container.clone();
-        let join = tokio::spawn(async move {
-            let top_inner_cosa = cosa.clone();
-            let mut join_result: Result<(), CryptoError> = Ok(());
-
-            info!("Listener ready");
-            while let Some(next) = read.next().await {
-                let inner_cosa = top_inner_cosa.clone();
-                match next {
-                    Ok(message) => {
-                        let e = events.lock().await;
-                        match message {
-                            Message::Text(text) => {
-                                debug!("Text received {text}");
-                                // Json parse
-                                match serde_json::from_str::<message::Message>(&text) {
-                                    Ok(msg) => match msg {
-                                        message::Message::HeartbeatRequest { id } => {
-                                            debug!("heartbeat received");
-                                            let message =
-                                                subscription::Request::HeartbeatResponse { id };
-                                            match serde_json::to_string(&message) {
-                                                Ok(text) => {
-                                                    if let Err(error) = inner_writer
-                                                        .lock()
-                                                        .await
-                                                        .send(Message::text(text))
-                                                        .await
-                                                    {
-                                                        error!("Cannot send heartbeat");
-                                                        e(
-                                                            Err(CryptoError::TungsteniteError(
-                                                                error,
-                                                            )),
-                                                            inner_cosa,
-                                                        );
-                                                    } else {
-                                                        debug!("heartbeat sent");
-                                                    }
-                                                }
-                                                Err(error) => {
-                                                    error!("Cannot serialize heartbeat");
-                                                    e(
-                                                        Err(CryptoError::SerdeError(error)),
-                                                        inner_cosa,
-                                                    );
-                                                }
-                                            }
-                                        }
-                                        message::Message::SubscriptionResponse {
-                                            result,
-                                            id,
-                                            code,
-                                            channel,
-                                            message,
-                                        } => {
-                                            if let Some(result) = result {
-                                                debug!("Message received: {:?}", result);
-                                                e(Ok(result), inner_cosa).await;
-                                            } else if code != 0 {
-                                                e(
-                                                    Err(CryptoError::SubscriptionError {
-                                                        id,
-                                                        code,
-                                                        message,
-                                                        channel,
-                                                    }),
-                                                    inner_cosa,
-                                                );
-                                            }
-                                        }
-                                        message::Message::UnsubscriptionResponse { id, code } => {
-                                            debug!("Unsubscription: {id} {code}");
-                                            e(
-                                                Ok(SubscribeResult::UnsubscriptionResult {
-                                                    success: code == 0,
-                                                }),
-                                                inner_cosa,
-                                            )
-                                            .await;
-                                        }
-                                        message::Message::AuthResponse { id, code } => {
-                                            debug!("Notify auth response: {id} {code}");
-                                            e(
-                                                Ok(SubscribeResult::AuthResult {
-                                                    success: code == 0,
-                                                }),
-                                                inner_cosa,
-                                            )
-                                            .await;
-                                        }
-                                    },
-                                    Err(err) => {
-                                        error!("Error when parsing JSON:\n{}\n{}", text, err);
-                                        e(Err(CryptoError::SerdeError(err)), inner_cosa).await;
-                                    }
-                                }
-                            }
-                            Message::Ping(message) => {
-                                debug!("Ping received {:?}", message);
-                                if let Err(error) =
-                                    inner_writer.lock().await.send(Message::Pong(message)).await
-                                {
-                                    error!("Cannot send pong");
-                                    e(Err(CryptoError::TungsteniteError(error)), inner_cosa).await;
-                                } else {
-                                    debug!("Pong sent");
-                                }
-                            }
-                            Message::Pong(message) => {
-                                debug!("PONG RECEIVED {:?}", message);
-                            }
-                            Message::Close(frame) => {
-                                e(
-                                    Err(CryptoError::CloseError {
-                                        frame: frame.clone(),
-                                    }),
-                                    inner_cosa,
-                                )
-                                .await;
-                                return Err(CryptoError::CloseError { frame });
-                            }
-                            message => {
-                                error!("Unexpected message {:?}", message);
-                                e(
-                                    Err(CryptoError::UnexpectedMessageError { message }),
-                                    inner_cosa,
-                                )
-                                .await;
-                            }
-                        }
-                    }
-                    Err(error) => {
-                        let e = events.lock().await;
-                        error!("Websocket read error: {:?}", error);
-                        e(
-                            Err(CryptoError::TungsteniteErrorString(error.to_string())),
-                            inner_cosa,
-                        )
-                        .await;
-                        join_result = Err(CryptoError::TungsteniteError(error));
-                    }
-                }
+        self.shared.health.set_state(ConnectionState::Connecting);
+        let (write, read) = match open(uri, &self.config).await {
+            Ok(connection) => connection,
+            Err(error) => {
+                self.shared.health.set_state(ConnectionState::Disconnected);
+                return Err(error);
             }
-            join_result
-        });
+        };
+        *self.shared.writer.lock().await = Some(write);
+        self.shared.authenticated.store(false, Ordering::SeqCst);
+        self.shared.closing.store(false, Ordering::SeqCst);
 
-        self.reader_join = Some(join);
-        self.writer = Some(writer);
+        let (deliveries, queue) = unbounded_channel();
+        self.deliveries = Some(deliveries.downgrade());
+        let reader = Reader {
+            events: Arc::clone(&self.events),
+            container: self.container.clone(),
+            deliveries,
+            shared: Arc::clone(&self.shared),
+            config: self.config.clone(),
+            uri: uri.to_owned(),
+            resubscribe_on_auth: false,
+            reauthenticating: false,
+            reauth_attempts: 0,
+            maintenance: false,
+        };
+        self.shared.health.set_state(ConnectionState::Connected);
+        self.shared.health.set_reconnect_attempt(0);
+        self.reader_join = Some(tokio::spawn(reader.run(read, queue)));
         info!("Connected");
         Ok(())
     }
 
     pub async fn subscribe(&mut self, param: Value) -> Result<(), CryptoError> {
         debug!("Subscribing to {:?} param", param);
-        if let Some(writer) = self.writer.as_mut() {
-            let message = subscription::Request::Subscribe {
-                id: self.message_id,
-                params: param,
+        let channels = checked_channels(&param, self.config.allow_unknown_channels)?;
+        let Some(deliveries) = self.deliveries.clone() else {
+            return Err(CryptoError::NotConnectedError);
+        };
+        if !self.shared.is_connected().await {
+            return Err(CryptoError::NotConnectedError);
+        }
+        let id = self.shared.next_id();
+        let message = subscription::Request::Subscribe {
+            id,
+            params: param,
+            nonce: nonce(),
+        };
+        let timer = spawn_ack_timer(
+            Arc::clone(&self.shared),
+            deliveries,
+            id,
+            self.config.ack_timeout,
+        );
+        self.shared.wait_ack(id, channels.clone(), timer).await;
+        if let Err(error) = self.shared.send_limited(&message).await {
+            self.shared.cancel_ack(id).await;
+            return Err(error);
+        }
+        self.shared.subscriptions.lock().await.extend(channels);
+        Ok(())
+    }
+
+    /// Send a text frame as is
+    pub async fn send_raw(&mut self, text: String) -> Result<(), CryptoError> {
+        self.shared.send(Message::text(text)).await
+    }
+
+    /// Answer the heartbeat `id`, only needed with `HeartbeatMode::Manual`
+    pub async fn respond_heartbeat(&mut self, id: u64) -> Result<(), CryptoError> {
+        self.shared
+            .send_request(&subscription::Request::HeartbeatResponse { id })
+            .await?;
+        self.shared.health.heartbeat_answered();
+        Ok(())
+    }
+
+    /// Send a request and wait for its response, returned with the id of the request
+    async fn request(
+        &mut self,
+        build: impl FnOnce(u64) -> subscription::Request,
+    ) -> Result<(u64, Response), CryptoError> {
+        let timeout = self.config.request_timeout;
+        self.request_with_timeout(build, timeout).await
+    }
+
+    async fn request_with_timeout(
+        &mut self,
+        build: impl FnOnce(u64) -> subscription::Request,
+        timeout: Duration,
+    ) -> Result<(u64, Response), CryptoError> {
+        if !self.shared.is_connected().await {
+            return Err(CryptoError::NotConnectedError);
+        }
+        let id = self.shared.next_id();
+        let request = build(id);
+        let (sender, receiver) = oneshot::channel();
+        self.shared.pending.lock().await.insert(id, sender);
+        if let Err(error) = self.shared.send_limited(&request).await {
+            self.shared.pending.lock().await.remove(&id);
+            return Err(error);
+        }
+
+        match tokio::time::timeout(timeout, receiver).await {
+            Ok(Ok(result)) => result.map(|response| (id, response)),
+            Ok(Err(_)) => Err(CryptoError::NotConnectedError),
+            Err(_) => {
+                self.shared.pending.lock().await.remove(&id);
+                Err(CryptoError::RequestTimeout { id })
+            }
+        }
+    }
+
+    /// Same as `request` but the connection has to be authenticated
+    async fn private_request(
+        &mut self,
+        build: impl FnOnce(u64) -> subscription::Request,
+    ) -> Result<(u64, Response), CryptoError> {
+        if !self.shared.is_connected().await {
+            return Err(CryptoError::NotConnectedError);
+        }
+        if !self.is_authenticated() {
+            return Err(CryptoError::NotAuthenticatedError);
+        }
+        self.request(build).await
+    }
+
+    /// Request the metadata of every instrument of the exchange
+    pub async fn get_instruments(&mut self) -> Result<Vec<InstrumentInfo>, CryptoError> {
+        let (id, response) = self
+            .request(|id| subscription::Request::GetInstruments { id, nonce: nonce() })
+            .await?;
+        match response {
+            Response::Instruments(result) => Ok(result.data),
+            other => Err(unexpected(id, &other)),
+        }
+    }
+
+    /// Request a snapshot of the book of `instrument_name` with up to `depth` levels.
+    /// `None` if the response has no snapshot.
+    pub async fn get_book(
+        &mut self,
+        instrument_name: &str,
+        depth: i32,
+    ) -> Result<Option<Book>, CryptoError> {
+        let params = subscription::GetBookParams {
+            instrument_name: instrument_name.to_owned(),
+            depth,
+        };
+        let (id, response) = self
+            .request(|id| subscription::Request::GetBook {
+                id,
+                params,
                 nonce: nonce(),
-            };
+            })
+            .await?;
+        match response {
+            Response::Book(result) => Ok(result.and_then(|result| result.data.into_iter().next())),
+            other => Err(unexpected(id, &other)),
+        }
+    }
 
-            let text = serde_json::to_string(&message)?;
-            writer.lock().await.send(Message::text(text)).await?;
-            // Increase message_id only if the message was actually sent
-            self.message_id += 1;
-            debug!("New message id {:?}", self.message_id);
-            Ok(())
-        } else {
-            Err(CryptoError::NotConnectedError)
+    /// Request the balances of the account, only of `currency` if present.
+    /// The connection has to be authenticated.
+    pub async fn get_account_summary(
+        &mut self,
+        currency: Option<&str>,
+    ) -> Result<Vec<Balance>, CryptoError> {
+        let params = subscription::AccountSummaryParams {
+            currency: currency.map(str::to_owned),
+        };
+        let (id, response) = self
+            .private_request(|id| subscription::Request::GetAccountSummary {
+                id,
+                params,
+                nonce: nonce(),
+            })
+            .await?;
+        match response {
+            Response::AccountSummary(result) => Ok(result.accounts),
+            other => Err(unexpected(id, &other)),
         }
     }
 
-    pub async fn unsubscribe(&mut self, channels: Vec<String>) -> Result<(), CryptoError> {
-        debug!("Unsubscribing to {:?} channels", channels.len());
-        if let Some(writer) = self.writer.as_mut() {
-            let message = subscription::Request::Unsubscribe {
-                id: self.message_id,
-                params: subscription::UnsubscribeParams { channels },
+    /// Request a page of the open orders, only of `instrument_name` if present.
+    /// The connection has to be authenticated.
+    pub async fn get_open_orders(
+        &mut self,
+        instrument_name: Option<&str>,
+        page_size: Option<u32>,
+        page: Option<u32>,
+    ) -> Result<OrderPage, CryptoError> {
+        let params = subscription::OrderListParams {
+            instrument_name: instrument_name.map(str::to_owned),
+            page_size,
+            page,
+            ..Default::default()
+        };
+        let (id, response) = self
+            .private_request(|id| subscription::Request::GetOpenOrders {
+                id,
+                params,
                 nonce: nonce(),
-            };
+            })
+            .await?;
+        match response {
+            Response::OpenOrders(result) => Ok(OrderPage {
+                count: result.count,
+                page: page.unwrap_or(0),
+                orders: result.order_list,
+            }),
+            other => Err(unexpected(id, &other)),
+        }
+    }
 
-            let text = serde_json::to_string(&message)?;
-            writer.lock().await.send(Message::text(text)).await?;
-            // Increase message_id only if the message was actually sent
-            self.message_id += 1;
-            debug!("New message id {:?}", self.message_id);
-            Ok(())
-        } else {
-            Err(CryptoError::NotConnectedError)
+    /// Request a page of the order history between `start_ts` and `end_ts` (millis since
+    /// epoch), only of `instrument_name` if present. The connection has to be authenticated.
+    pub async fn get_order_history(
+        &mut self,
+        instrument_name: Option<&str>,
+        start_ts: Option<u64>,
+        end_ts: Option<u64>,
+        page_size: Option<u32>,
+        page: Option<u32>,
+    ) -> Result<OrderPage, CryptoError> {
+        let params = subscription::OrderListParams {
+            instrument_name: instrument_name.map(str::to_owned),
+            start_ts,
+            end_ts,
+            page_size,
+            page,
+        };
+        let (id, response) = self
+            .private_request(|id| subscription::Request::GetOrderHistory {
+                id,
+                params,
+                nonce: nonce(),
+            })
+            .await?;
+        match response {
+            Response::OrderHistory(result) => Ok(OrderPage {
+                count: result.count,
+                page: page.unwrap_or(0),
+                orders: result.order_list,
+            }),
+            other => Err(unexpected(id, &other)),
         }
     }
 
-    pub async fn auth(&mut self, api_key: &str, api_secret: &str) -> Result<(), CryptoError> {
-        if let Some(writer) = self.writer.as_mut() {
-            let n = nonce();
-            let message_to_sig = [
-                "public/auth".into(),
-                self.message_id.to_string(),
-                api_key.to_owned(),
-                n.to_string(),
-            ]
-            .concat();
-            let mut mac = HmacSha256::new_from_slice(api_secret.as_bytes())?;
-            mac.update(message_to_sig.as_bytes());
-            let result = mac.finalize();
-            let f = result.into_bytes();
-
-            let message = subscription::Request::Auth {
-                id: self.message_id,
-                api_key: api_key.to_owned(),
-                sig: hex::encode(f),
-                nonce: n,
-            };
+    /// Subscribe to the order updates of `instrument_name`, requires an authenticated user connection
+    pub async fn subscribe_user_order(&mut self, instrument_name: &str) -> Result<(), CryptoError> {
+        self.subscribe_channels(vec![model::user_order(instrument_name)])
+            .await
+    }
 
-            let text = serde_json::to_string(&message)?;
-            writer.lock().await.send(Message::text(text)).await?;
-            // Increase message_id only if the message was actually sent
-            self.message_id += 1;
-            Ok(())
-        } else {
-            Err(CryptoError::NotConnectedError)
+    /// Subscribe to the executions of the orders on `instrument_name`, requires an authenticated user connection
+    pub async fn subscribe_user_trade(&mut self, instrument_name: &str) -> Result<(), CryptoError> {
+        self.subscribe_channels(vec![model::user_trade(instrument_name)])
+            .await
+    }
+
+    /// Subscribe to the index price of `instrument_name`, for example `BTCUSD-INDEX`
+    pub async fn subscribe_index(&mut self, instrument_name: &str) -> Result<(), CryptoError> {
+        self.subscribe_channels(vec![model::index(instrument_name)])
+            .await
+    }
+
+    /// Subscribe to the mark price of `instrument_name`, for example `BTCUSD-PERP`
+    pub async fn subscribe_mark_price(&mut self, instrument_name: &str) -> Result<(), CryptoError> {
+        self.subscribe_channels(vec![model::mark(instrument_name)])
+            .await
+    }
+
+    /// Subscribe to the settlement price of the expiring future `instrument_name`
+    pub async fn subscribe_settlement(&mut self, instrument_name: &str) -> Result<(), CryptoError> {
+        self.subscribe_channels(vec![model::settlement(instrument_name)])
+            .await
+    }
+
+    /// Subscribe to the funding rate of the perpetual `instrument_name`
+    pub async fn subscribe_funding(&mut self, instrument_name: &str) -> Result<(), CryptoError> {
+        self.subscribe_channels(vec![model::funding(instrument_name)])
+            .await
+    }
+
+    /// Subscribe to the estimated funding rate of the perpetual `instrument_name`
+    pub async fn subscribe_estimated_funding(
+        &mut self,
+        instrument_name: &str,
+    ) -> Result<(), CryptoError> {
+        self.subscribe_channels(vec![model::estimated_funding(instrument_name)])
+            .await
+    }
+
+    /// Subscribe to the OTC book of `instrument_name`
+    pub async fn subscribe_otc_book(&mut self, instrument_name: &str) -> Result<(), CryptoError> {
+        self.subscribe_channels(vec![model::otc_book(instrument_name)])
+            .await
+    }
+
+    /// Subscribe to the tickers of every instrument in one channel. The handlers of
+    /// `on_channel` for the ticker of an instrument get its tickers too
+    pub async fn subscribe_all_tickers(&mut self) -> Result<(), CryptoError> {
+        self.subscribe_channels(vec![model::all_tickers()]).await
+    }
+
+    /// Subscribe to the position updates, requires an authenticated user connection
+    pub async fn subscribe_positions(&mut self) -> Result<(), CryptoError> {
+        self.subscribe_channels(vec![model::positions()]).await
+    }
+
+    /// Subscribe to the position and balance updates, requires an authenticated user connection
+    pub async fn subscribe_position_balance(&mut self) -> Result<(), CryptoError> {
+        self.subscribe_channels(vec![model::position_balance()])
+            .await
+    }
+
+    async fn subscribe_channels(&mut self, channels: Vec<String>) -> Result<(), CryptoError> {
+        let params = serde_json::to_value(subscription::SubscribeParams { channels })?;
+        self.subscribe(params).await
+    }
+
+    pub async fn unsubscribe(&mut self, channels: Vec<String>) -> Result<(), CryptoError> {
+        debug!("Unsubscribing to {:?} channels", channels.len());
+        if !self.shared.is_connected().await {
+            return Err(CryptoError::NotConnectedError);
+        }
+        let message = subscription::Request::Unsubscribe {
+            id: self.shared.next_id(),
+            params: subscription::UnsubscribeParams {
+                channels: channels.clone(),
+            },
+            nonce: nonce(),
+        };
+        self.shared.send_limited(&message).await?;
+        let mut subscriptions = self.shared.subscriptions.lock().await;
+        for channel in &channels {
+            subscriptions.remove(channel);
+        }
+        Ok(())
+    }
+
+    pub async fn auth(&mut self, api_key: &str, api_secret: &str) -> Result<(), CryptoError> {
+        if !self.shared.is_connected().await {
+            return Err(CryptoError::NotConnectedError);
         }
+        let message = auth_request(self.shared.next_id(), api_key, api_secret)?;
+        self.shared.send_limited(&message).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::{BackoffPolicy, CryptoClientBuilder, RateLimit};
+    use crate::mock::MockServer;
+    use crate::SubscribeResult;
+    use tokio::sync::mpsc::{unbounded_channel, UnboundedSender};
+    use tokio_tungstenite::tungstenite::protocol::frame::coding::CloseCode;
+
+    const TRADE: &str = "{\"method\":\"subscribe\",\"id\":1,\"code\":0,\"result\":{\"channel\":\"trade\",\"instrument_name\":\"ETH_CRO\",\"subscription\":\"trade.ETH_CRO\",\"data\":[]}}";
+
+    fn envelope_client(
+        raw_passthrough: bool,
+    ) -> (
+        CryptoClient<impl Future<Output = ()> + Send + Sync + 'static, UnboundedSender<Envelope>>,
+        tokio::sync::mpsc::UnboundedReceiver<Envelope>,
+    ) {
+        let (sender, receiver) = unbounded_channel::<Envelope>();
+        let client = CryptoClientBuilder::new_with_envelope(
+            |envelope: Envelope, sender: UnboundedSender<Envelope>| async move {
+                sender.send(envelope).ok();
+            },
+            sender,
+        )
+        .with_raw_passthrough(raw_passthrough)
+        .build()
+        .unwrap();
+        (client, receiver)
+    }
+
+    /// Frame texts copied by the readers running on this thread
+    fn raw_copies() -> usize {
+        crate::reader::RAW_COPIES.with(|copies| copies.get())
+    }
+
+    #[tokio::test]
+    async fn raw_passthrough_keeps_the_frame_text() {
+        let server = MockServer::start().await;
+        let (mut client, mut receiver) = envelope_client(true);
+        client.connect(&server.url).await.unwrap();
+        let copies = raw_copies();
+
+        let unparsable =
+            "{\"method\":\"subscribe\",\"id\":1,\"code\":0,\"result\":{\"channel\":\"trade\"}}";
+        server.push_text(TRADE);
+        server.push_text(unparsable);
+        server.push_text(BOOK);
+
+        let envelope = receiver.recv().await.unwrap();
+        assert!(matches!(
+            envelope.result,
+            Ok(SubscribeResult::TradeResult(_))
+        ));
+        assert_eq!(envelope.raw.as_deref(), Some(TRADE));
+        let envelope = receiver.recv().await.unwrap();
+        assert!(matches!(envelope.result, Err(CryptoError::SerdeError(_))));
+        assert_eq!(envelope.raw.as_deref(), Some(unparsable));
+        let envelope = receiver.recv().await.unwrap();
+        assert!(matches!(
+            envelope.result,
+            Ok(SubscribeResult::BookResult(_))
+        ));
+        assert_eq!(envelope.raw.as_deref(), Some(BOOK));
+        assert_eq!(raw_copies(), copies + 3);
+
+        // Not coming from a frame
+        server.push(Message::Close(None));
+        let envelope = receiver.recv().await.unwrap();
+        assert!(matches!(
+            envelope.result,
+            Err(CryptoError::CloseError { .. })
+        ));
+        assert!(envelope.raw.is_none());
+        client.disconnect_fast().await.ok();
+    }
+
+    #[tokio::test]
+    async fn raw_text_is_not_kept_by_default() {
+        let server = MockServer::start().await;
+        let (mut client, mut receiver) = envelope_client(false);
+        client.connect(&server.url).await.unwrap();
+        let copies = raw_copies();
+
+        server.push_text(TRADE);
+        server.push_text("not json");
+        for _ in 0..2 {
+            assert!(receiver.recv().await.unwrap().raw.is_none());
+        }
+        // The reader runs on the thread of the test, the text was never copied
+        assert_eq!(raw_copies(), copies);
+        client.disconnect_fast().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn envelopes_have_non_decreasing_receive_times() {
+        let server = MockServer::start().await;
+        for _ in 0..100 {
+            server.push_text(TRADE);
+        }
+
+        let (sender, mut receiver) = unbounded_channel::<Envelope>();
+        let mut client = CryptoClient::new_with_envelope(
+            |envelope: Envelope, sender: UnboundedSender<Envelope>| async move {
+                sender.send(envelope).ok();
+            },
+            sender,
+        );
+        let before = SystemTime::now();
+        client.connect(&server.url).await.unwrap();
+
+        let mut previous: Option<Envelope> = None;
+        for _ in 0..100 {
+            let envelope = receiver.recv().await.unwrap();
+            assert!(matches!(
+                envelope.result,
+                Ok(SubscribeResult::TradeResult(_))
+            ));
+            assert!(envelope.received_at >= before);
+            if let Some(previous) = previous {
+                assert!(envelope.received_instant >= previous.received_instant);
+                assert!(envelope.received_at >= previous.received_at);
+            }
+            previous = Some(envelope);
+        }
+        client.disconnect().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn plain_callback_receives_the_result() {
+        let server = MockServer::start().await;
+        server.push_text(TRADE);
+
+        let (sender, mut receiver) = unbounded_channel::<bool>();
+        let mut client = CryptoClient::new(
+            |result: Result<SubscribeResult, CryptoError>, sender: UnboundedSender<bool>| async move {
+                sender
+                    .send(matches!(result, Ok(SubscribeResult::TradeResult(_))))
+                    .ok();
+            },
+            sender,
+        );
+        client.connect(&server.url).await.unwrap();
+
+        assert!(receiver.recv().await.unwrap());
+        client.disconnect().await.unwrap();
+    }
+
+    fn silent_client() -> CryptoClient<impl Future<Output = ()> + Send + Sync + 'static, ()> {
+        CryptoClient::new(
+            |_: Result<SubscribeResult, CryptoError>, _: ()| async {},
+            (),
+        )
+    }
+
+    #[tokio::test]
+    async fn invalid_channels_are_not_sent() {
+        let mut server = MockServer::start().await;
+        let mut client = silent_client();
+        client.connect(&server.url).await.unwrap();
+
+        let invalid = [
+            (serde_json::json!({}), "`channels` array"),
+            (
+                serde_json::json!({"channels": "trade.ETH_CRO"}),
+                "`channels` array",
+            ),
+            (serde_json::json!({"channels": [42]}), "strings"),
+            (
+                serde_json::json!({"channels": ["book.ETH_CRO.1O"]}),
+                "depth",
+            ),
+            (serde_json::json!({"channels": ["book.ETH_CRO"]}), "depth"),
+            (
+                serde_json::json!({"channels": ["candlestick.2m.ETH_CRO"]}),
+                "time frame",
+            ),
+            (
+                serde_json::json!({"channels": ["ticker.ETH_CRO", "trades.ETH_CRO"]}),
+                "unknown channel",
+            ),
+            (serde_json::json!({"channels": ["mark"]}), "instrument"),
+        ];
+        for (params, expected) in invalid {
+            match client.subscribe(params.clone()).await {
+                Err(CryptoError::InvalidChannel { reason, .. }) => {
+                    assert!(reason.contains(expected), "{params}: {reason}")
+                }
+                other => panic!("{params}: unexpected {other:?}"),
+            }
+        }
+        assert!(server
+            .recv_timeout(Duration::from_millis(100))
+            .await
+            .is_none());
+
+        let channels = serde_json::json!([
+            "book.ETH_CRO.10",
+            "candlestick.1h.ETH_CRO",
+            "ticker.ETH_CRO",
+            "trade.ETH_CRO",
+            "otc_book.BTC_USDT",
+            "index.BTCUSD-INDEX",
+            "mark.BTCUSD-PERP",
+            "settlement.BTCUSD-PERP",
+            "funding.BTCUSD-PERP",
+            "estimatedfunding.BTCUSD-PERP",
+            "user.order.ETH_CRO",
+            "user.trade.ETH_CRO",
+            "user.balance",
+        ]);
+        client
+            .subscribe(serde_json::json!({ "channels": channels }))
+            .await
+            .unwrap();
+        let request = server.recv_request().await.unwrap();
+        assert_eq!(request["params"]["channels"], channels);
+        client.disconnect_fast().await.unwrap();
+    }
+
+    /// Answers the trade channels with data and rejects the others
+    fn mixed_response(request: &Value) -> Vec<Message> {
+        if request["method"] != "subscribe" {
+            return Vec::new();
+        }
+        let id = &request["id"];
+        let channels = request["params"]["channels"].as_array().unwrap();
+        channels
+            .iter()
+            .map(|channel| {
+                let channel = channel.as_str().unwrap();
+                let response = match channel.split_once('.') {
+                    Some(("trade", instrument_name)) => serde_json::json!({
+                        "id": id, "method": "subscribe", "code": 0,
+                        "result": {
+                            "channel": "trade", "instrument_name": instrument_name,
+                            "subscription": channel, "data": [],
+                        },
+                    }),
+                    Some(("ticker", _)) => serde_json::json!({
+                        "id": id, "method": "subscribe", "code": 10004,
+                        "message": "BAD_REQUEST", "channel": channel,
+                    }),
+                    _ => serde_json::json!({
+                        "id": id, "method": "subscribe", "code": 40003,
+                        "message": "BAD_INSTRUMENT", "channel": channel,
+                    }),
+                };
+                Message::text(response.to_string())
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn partial_subscription_reports_the_rejected_channels() {
+        let mut server = MockServer::with_responder(mixed_response).await;
+        let (builder, mut receiver) = forwarding_builder();
+        let mut client = builder
+            .with_backoff_policy(fast_backoff(None))
+            .build()
+            .unwrap();
+        client.connect(&server.url).await.unwrap();
+
+        client
+            .subscribe(serde_json::json!({
+                "channels": ["ticker.NOPE_CRO", "trade.ETH_CRO", "index.NOPE-INDEX"]
+            }))
+            .await
+            .unwrap();
+        server.recv_request().await.unwrap();
+        assert!(matches!(
+            receiver.recv().await.unwrap(),
+            Ok(SubscribeResult::TradeResult(_))
+        ));
+        match receiver.recv().await.unwrap() {
+            Ok(SubscribeResult::PartialSubscription { accepted, rejected }) => {
+                assert_eq!(accepted, vec!["trade.ETH_CRO".to_owned()]);
+                assert_eq!(
+                    rejected,
+                    vec![
+                        ("ticker.NOPE_CRO".to_owned(), 10004),
+                        ("index.NOPE-INDEX".to_owned(), 40003)
+                    ]
+                );
+            }
+            other => panic!("unexpected {other:?}"),
+        }
+
+        // Only the accepted channels are subscribed again
+        server.push(Message::Close(None));
+        assert!(matches!(
+            receiver.recv().await.unwrap(),
+            Err(CryptoError::CloseError { .. })
+        ));
+        assert!(matches!(
+            receiver.recv().await.unwrap(),
+            Ok(SubscribeResult::Reconnected { attempt: 1 })
+        ));
+        let request = server.recv_request().await.unwrap();
+        assert_eq!(
+            request["params"]["channels"],
+            serde_json::json!(["trade.ETH_CRO"])
+        );
+        assert!(matches!(
+            receiver.recv().await.unwrap(),
+            Ok(SubscribeResult::TradeResult(_))
+        ));
+        assert!(receiver.try_recv().is_err());
+        client.disconnect_fast().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn rejections_before_the_timeout_are_reported() {
+        let server = MockServer::with_responder(|request| {
+            // The trade channel is never answered
+            mixed_response(request)
+                .into_iter()
+                .filter(|message| !message.to_text().unwrap().contains("\"code\":0"))
+                .collect()
+        })
+        .await;
+        let (builder, mut receiver) = forwarding_builder();
+        let mut client = builder
+            .with_ack_timeout(Duration::from_millis(100))
+            .build()
+            .unwrap();
+        client.connect(&server.url).await.unwrap();
+
+        client
+            .subscribe(serde_json::json!({"channels": ["trade.ETH_CRO", "ticker.NOPE_CRO"]}))
+            .await
+            .unwrap();
+        match receiver.recv().await.unwrap() {
+            Ok(SubscribeResult::PartialSubscription { accepted, rejected }) => {
+                assert!(accepted.is_empty());
+                assert_eq!(rejected, vec![("ticker.NOPE_CRO".to_owned(), 10004)]);
+            }
+            other => panic!("unexpected {other:?}"),
+        }
+        match receiver.recv().await.unwrap() {
+            Err(CryptoError::AckTimeout { id, channels }) => {
+                assert_eq!(id, 1);
+                assert_eq!(channels, vec!["trade.ETH_CRO".to_owned()]);
+            }
+            other => panic!("unexpected {other:?}"),
+        }
+        client.disconnect_fast().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn unknown_channels_can_be_allowed() {
+        let mut server = MockServer::start().await;
+        let (builder, _receiver) = forwarding_builder();
+        let mut client = builder.allow_unknown_channels(true).build().unwrap();
+        client.connect(&server.url).await.unwrap();
+
+        client
+            .subscribe(serde_json::json!({"channels": ["liquidation.ETH_CRO"]}))
+            .await
+            .unwrap();
+        let request = server.recv_request().await.unwrap();
+        assert_eq!(
+            request["params"]["channels"],
+            serde_json::json!(["liquidation.ETH_CRO"])
+        );
+
+        // Only the unknown channels are allowed
+        assert!(matches!(
+            client
+                .subscribe(serde_json::json!({"channels": ["book.ETH_CRO.1O"]}))
+                .await,
+            Err(CryptoError::InvalidChannel { channel, .. }) if channel == "book.ETH_CRO.1O"
+        ));
+        client.disconnect_fast().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn typed_subscribe_helpers_send_the_channel() {
+        let mut server = MockServer::start().await;
+        let mut client = silent_client();
+        client.connect(&server.url).await.unwrap();
+
+        client.subscribe_index("BTCUSD-INDEX").await.unwrap();
+        let request = server.recv_request().await.unwrap();
+        assert_eq!(request["method"], "subscribe");
+        assert_eq!(request["params"]["channels"][0], "index.BTCUSD-INDEX");
+
+        client.subscribe_mark_price("BTCUSD-PERP").await.unwrap();
+        let request = server.recv_request().await.unwrap();
+        assert_eq!(request["params"]["channels"][0], "mark.BTCUSD-PERP");
+
+        client
+            .subscribe_settlement("BTCUSD-210528m2")
+            .await
+            .unwrap();
+        let request = server.recv_request().await.unwrap();
+        assert_eq!(
+            request["params"]["channels"][0],
+            "settlement.BTCUSD-210528m2"
+        );
+
+        client.subscribe_funding("BTCUSD-PERP").await.unwrap();
+        let request = server.recv_request().await.unwrap();
+        assert_eq!(request["params"]["channels"][0], "funding.BTCUSD-PERP");
+
+        client
+            .subscribe_estimated_funding("BTCUSD-PERP")
+            .await
+            .unwrap();
+        let request = server.recv_request().await.unwrap();
+        assert_eq!(
+            request["params"]["channels"][0],
+            "estimatedfunding.BTCUSD-PERP"
+        );
+
+        client.subscribe_otc_book("BTC_USDT").await.unwrap();
+        let request = server.recv_request().await.unwrap();
+        assert_eq!(request["params"]["channels"][0], "otc_book.BTC_USDT");
+
+        client.subscribe_positions().await.unwrap();
+        let request = server.recv_request().await.unwrap();
+        assert_eq!(request["params"]["channels"][0], "user.positions");
+
+        client.subscribe_position_balance().await.unwrap();
+        let request = server.recv_request().await.unwrap();
+        assert_eq!(request["params"]["channels"][0], "user.position_balance");
+
+        client.subscribe_user_order("ETH_CRO").await.unwrap();
+        let request = server.recv_request().await.unwrap();
+        assert_eq!(request["params"]["channels"][0], "user.order.ETH_CRO");
+
+        client.subscribe_user_trade("ETH_CRO").await.unwrap();
+        let request = server.recv_request().await.unwrap();
+        assert_eq!(request["params"]["channels"][0], "user.trade.ETH_CRO");
+
+        client.disconnect().await.unwrap();
+    }
+
+    fn instruments_response(request: &Value) -> Vec<Message> {
+        if request["method"] != "public/get-instruments" {
+            return Vec::new();
+        }
+        let response = serde_json::json!({
+            "id": request["id"],
+            "method": "public/get-instruments",
+            "code": 0,
+            "result": {
+                "data": [
+                    {
+                        "instrument_name": "ETH_CRO",
+                        "quote_currency": "CRO",
+                        "base_currency": "ETH",
+                        "price_decimals": 2,
+                        "quantity_decimals": 2,
+                        "margin_trading_enabled": false
+                    },
+                    {
+                        "instrument_name": "BTC_USDT",
+                        "quote_currency": "USDT",
+                        "base_currency": "BTC",
+                        "price_decimals": 2,
+                        "quantity_decimals": 6,
+                        "margin_trading_enabled": true,
+                        "min_quantity": "0.0001"
+                    }
+                ]
+            }
+        });
+        vec![Message::text(response.to_string())]
+    }
+
+    #[tokio::test]
+    async fn get_instruments_returns_the_correlated_response() {
+        let mut server = MockServer::with_responder(instruments_response).await;
+        let mut client = silent_client();
+        client.connect(&server.url).await.unwrap();
+
+        let instruments = client.get_instruments().await.unwrap();
+        assert_eq!(instruments.len(), 2);
+        assert_eq!(instruments[0].instrument_name, "ETH_CRO");
+        assert_eq!(instruments[1].quantity_decimals, 6);
+        assert_eq!(instruments[1].min_quantity, Some(0.0001));
+
+        let request = server.recv_request().await.unwrap();
+        assert_eq!(request["method"], "public/get-instruments");
+        assert!(request["nonce"].is_u64());
+
+        // A second request gets its own id
+        client.get_instruments().await.unwrap();
+        let second = server.recv_request().await.unwrap();
+        assert_ne!(request["id"], second["id"]);
+
+        client.disconnect().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn get_instruments_handles_empty_and_errors() {
+        let server = MockServer::with_responder(|request| {
+            let response = if request["id"] == 1 {
+                serde_json::json!({"id": 1, "method": "public/get-instruments", "code": 0, "result": {"data": []}})
+            } else {
+                serde_json::json!({"id": request["id"], "method": "public/get-instruments", "code": 10004, "message": "BAD_REQUEST"})
+            };
+            vec![Message::text(response.to_string())]
+        })
+        .await;
+        let mut client = silent_client();
+        client.connect(&server.url).await.unwrap();
+
+        assert!(client.get_instruments().await.unwrap().is_empty());
+        match client.get_instruments().await {
+            Err(CryptoError::RequestError {
+                id, code, message, ..
+            }) => {
+                assert_eq!(id, 2);
+                assert_eq!(code, 10004);
+                assert_eq!(message.as_deref(), Some("BAD_REQUEST"));
+            }
+            other => panic!("unexpected {other:?}"),
+        }
+
+        client.disconnect().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn requests_time_out_without_response() {
+        let server = MockServer::start().await;
+        let mut client = CryptoClientBuilder::new(
+            |_: Result<SubscribeResult, CryptoError>, _: ()| async {},
+            (),
+        )
+        .with_request_timeout(Duration::from_millis(100))
+        .build()
+        .unwrap();
+        client.connect(&server.url).await.unwrap();
+
+        assert!(matches!(
+            client.get_instruments().await,
+            Err(CryptoError::RequestTimeout { id: 1 })
+        ));
+        assert!(client.shared.pending.lock().await.is_empty());
+
+        client.disconnect().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn requests_need_a_connection() {
+        let mut client = silent_client();
+        assert!(matches!(
+            client.get_instruments().await,
+            Err(CryptoError::NotConnectedError)
+        ));
+    }
+
+    #[tokio::test]
+    async fn get_book_returns_the_snapshot() {
+        let mut server = MockServer::with_responder(|request| {
+            let response = serde_json::json!({
+                "id": request["id"],
+                "method": "public/get-book",
+                "code": 0,
+                "result": {
+                    "instrument_name": request["params"]["instrument_name"],
+                    "depth": request["params"]["depth"],
+                    "data": [{
+                        "bids": [["11746.488", "128", "8"]],
+                        "asks": [["11747.488", "201", "12"]],
+                        "t": 1587523078844u64
+                    }]
+                }
+            });
+            vec![Message::text(response.to_string())]
+        })
+        .await;
+        let mut client = silent_client();
+        client.connect(&server.url).await.unwrap();
+
+        let snapshot = client.get_book("ETH_CRO", 150).await.unwrap().unwrap();
+        assert_eq!(snapshot.bids.len(), 1);
+        assert_eq!(snapshot.asks[0].price, 11747.488);
+        assert_eq!(snapshot.time, 1587523078844);
+
+        let request = server.recv_request().await.unwrap();
+        assert_eq!(request["method"], "public/get-book");
+        assert_eq!(request["params"]["instrument_name"], "ETH_CRO");
+        assert_eq!(request["params"]["depth"], 150);
+
+        client.disconnect().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn mismatched_responses_are_errors() {
+        // Every request is answered with a book response, empty for the second one
+        let server = MockServer::with_responder(|request| {
+            let response = if request["id"] == 2 {
+                serde_json::json!({"id": 2, "method": "public/get-book", "code": 0})
+            } else {
+                serde_json::json!({
+                    "id": request["id"], "method": "public/get-book", "code": 0,
+                    "result": {"instrument_name": "ETH_CRO", "depth": 10, "data": []}
+                })
+            };
+            vec![Message::text(response.to_string())]
+        })
+        .await;
+        let mut client = silent_client();
+        client.connect(&server.url).await.unwrap();
+
+        match client.get_instruments().await {
+            Err(CryptoError::UnexpectedResponse { id, method }) => {
+                assert_eq!(id, 1);
+                assert_eq!(method, "public/get-book");
+            }
+            other => panic!("unexpected {other:?}"),
+        }
+        assert!(client.get_book("ETH_CRO", 10).await.unwrap().is_none());
+        assert!(client.get_book("ETH_CRO", 10).await.unwrap().is_none());
+
+        client.disconnect().await.unwrap();
+    }
+
+    fn order(order_id: &str) -> Value {
+        serde_json::json!({
+            "status": "ACTIVE", "side": "BUY", "price": "1.5", "quantity": "2", "order_id": order_id,
+            "create_time": 1588758017375u64, "update_time": 1588758017411u64, "type": "LIMIT",
+            "instrument_name": "ETH_CRO", "cumulative_quantity": "0", "cumulative_value": "0",
+            "avg_price": "0", "fee_currency": "CRO", "time_in_force": "GOOD_TILL_CANCEL"
+        })
+    }
+
+    /// Answers auth requests successfully, account summaries with one balance per currency
+    /// and order requests with pages of one order
+    fn user_response(request: &Value) -> Vec<Message> {
+        let response = match request["method"].as_str() {
+            Some("private/get-open-orders") => {
+                let page = request["params"]["page"].as_u64().unwrap_or(0);
+                serde_json::json!({
+                    "id": request["id"],
+                    "method": "private/get-open-orders",
+                    "code": 0,
+                    "result": {"count": 3, "order_list": [order(&page.to_string())]}
+                })
+            }
+            Some("private/get-order-history") => {
+                if request["params"]["instrument_name"] == "BTC_USDT" {
+                    serde_json::json!({
+                        "id": request["id"],
+                        "method": "private/get-order-history",
+                        "code": 0,
+                        "result": {"order_list": []}
+                    })
+                } else {
+                    serde_json::json!({
+                        "id": request["id"],
+                        "method": "private/get-order-history",
+                        "code": 40003,
+                        "message": "BAD_SCOPE"
+                    })
+                }
+            }
+            Some("public/auth") => {
+                serde_json::json!({"id": request["id"], "method": "public/auth", "code": 0})
+            }
+            Some("private/get-account-summary") => {
+                let currencies = match request["params"]["currency"].as_str() {
+                    Some(currency) => vec![currency],
+                    None => vec!["CRO", "USDT"],
+                };
+                let accounts: Vec<Value> = currencies
+                    .into_iter()
+                    .map(|currency| {
+                        serde_json::json!({
+                            "balance": 10.5, "available": 9.5, "order": 1, "stake": 0, "currency": currency
+                        })
+                    })
+                    .collect();
+                serde_json::json!({
+                    "id": request["id"],
+                    "method": "private/get-account-summary",
+                    "code": 0,
+                    "result": {"accounts": accounts}
+                })
+            }
+            _ => return Vec::new(),
+        };
+        vec![Message::text(response.to_string())]
+    }
+
+    #[tokio::test]
+    async fn get_account_summary_after_auth() {
+        let mut server = MockServer::with_responder(user_response).await;
+        let (sender, mut receiver) = unbounded_channel::<SubscribeResult>();
+        let mut client = CryptoClient::new(
+            |result: Result<SubscribeResult, CryptoError>,
+             sender: UnboundedSender<SubscribeResult>| async move {
+                if let Ok(result) = result {
+                    sender.send(result).ok();
+                }
+            },
+            sender,
+        );
+        client.connect(&server.url).await.unwrap();
+        client.auth("key", "secret").await.unwrap();
+        assert!(matches!(
+            receiver.recv().await.unwrap(),
+            SubscribeResult::AuthResult { success: true }
+        ));
+        assert!(client.is_authenticated());
+        server.recv_request().await.unwrap();
+
+        let balances = client.get_account_summary(None).await.unwrap();
+        assert_eq!(balances.len(), 2);
+        assert_eq!(balances[0].currency, "CRO");
+        assert_eq!(balances[1].currency, "USDT");
+        assert_eq!(balances[1].available, 9.5);
+        let request = server.recv_request().await.unwrap();
+        assert_eq!(request["method"], "private/get-account-summary");
+        assert_eq!(request["params"], serde_json::json!({}));
+
+        let balances = client.get_account_summary(Some("CRO")).await.unwrap();
+        assert_eq!(balances.len(), 1);
+        assert_eq!(balances[0].currency, "CRO");
+        let request = server.recv_request().await.unwrap();
+        assert_eq!(request["params"], serde_json::json!({"currency": "CRO"}));
+
+        client.disconnect().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn get_account_summary_needs_auth() {
+        let mut client = silent_client();
+        assert!(matches!(
+            client.get_account_summary(None).await,
+            Err(CryptoError::NotConnectedError)
+        ));
+
+        let server = MockServer::with_responder(user_response).await;
+        client.connect(&server.url).await.unwrap();
+        assert!(matches!(
+            client.get_account_summary(Some("CRO")).await,
+            Err(CryptoError::NotAuthenticatedError)
+        ));
+
+        client.disconnect().await.unwrap();
+    }
+
+    async fn authenticated_client(
+        server: &mut MockServer,
+    ) -> CryptoClient<impl Future<Output = ()> + Send + Sync + 'static, ()> {
+        let mut client = silent_client();
+        client.connect(&server.url).await.unwrap();
+        client.auth("key", "secret").await.unwrap();
+        server.recv_request().await.unwrap();
+        while !client.is_authenticated() {
+            tokio::task::yield_now().await;
+        }
+        client
+    }
+
+    #[tokio::test]
+    async fn get_open_orders_pages() {
+        let mut server = MockServer::with_responder(user_response).await;
+        let mut client = authenticated_client(&mut server).await;
+
+        let page = client
+            .get_open_orders(Some("ETH_CRO"), Some(1), Some(2))
+            .await
+            .unwrap();
+        assert_eq!(page.count, Some(3));
+        assert_eq!(page.page, 2);
+        assert_eq!(page.orders.len(), 1);
+        assert_eq!(page.orders[0].order_id, "2");
+        assert_eq!(page.orders[0].price, 1.5);
+
+        let request = server.recv_request().await.unwrap();
+        assert_eq!(request["method"], "private/get-open-orders");
+        assert_eq!(
+            request["params"],
+            serde_json::json!({"instrument_name": "ETH_CRO", "page_size": 1, "page": 2})
+        );
+
+        let page = client.get_open_orders(None, None, None).await.unwrap();
+        assert_eq!(page.page, 0);
+        let request = server.recv_request().await.unwrap();
+        assert_eq!(request["params"], serde_json::json!({}));
+
+        client.disconnect().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn get_order_history_empty_and_errors() {
+        let mut server = MockServer::with_responder(user_response).await;
+        let mut client = authenticated_client(&mut server).await;
+
+        let page = client
+            .get_order_history(Some("BTC_USDT"), Some(1), Some(2), Some(20), None)
+            .await
+            .unwrap();
+        assert_eq!(page.count, None);
+        assert!(page.orders.is_empty());
+        let request = server.recv_request().await.unwrap();
+        assert_eq!(request["method"], "private/get-order-history");
+        assert_eq!(
+            request["params"],
+            serde_json::json!({"instrument_name": "BTC_USDT", "start_ts": 1, "end_ts": 2, "page_size": 20})
+        );
+
+        match client.get_order_history(None, None, None, None, None).await {
+            Err(CryptoError::RequestError {
+                method,
+                code,
+                message,
+                ..
+            }) => {
+                assert_eq!(method, "private/get-order-history");
+                assert_eq!(code, 40003);
+                assert_eq!(message.as_deref(), Some("BAD_SCOPE"));
+            }
+            other => panic!("unexpected {other:?}"),
+        }
+
+        client.disconnect().await.unwrap();
+    }
+
+    type Forwarded = Result<SubscribeResult, CryptoError>;
+
+    const HEARTBEAT: &str = "{\"id\":42,\"method\":\"public/heartbeat\"}";
+
+    fn forwarding_builder() -> (
+        CryptoClientBuilder<
+            impl Future<Output = ()> + Send + Sync + 'static,
+            UnboundedSender<Forwarded>,
+        >,
+        tokio::sync::mpsc::UnboundedReceiver<Forwarded>,
+    ) {
+        let (sender, receiver) = unbounded_channel();
+        let builder = CryptoClientBuilder::new(
+            |result: Forwarded, sender: UnboundedSender<Forwarded>| async move {
+                sender.send(result).ok();
+            },
+            sender,
+        );
+        (builder, receiver)
+    }
+
+    fn forwarding_client(
+        mode: HeartbeatMode,
+    ) -> (
+        CryptoClient<impl Future<Output = ()> + Send + Sync + 'static, UnboundedSender<Forwarded>>,
+        tokio::sync::mpsc::UnboundedReceiver<Forwarded>,
+    ) {
+        let (builder, receiver) = forwarding_builder();
+        let client = builder.with_heartbeat_mode(mode).build().unwrap();
+        (client, receiver)
+    }
+
+    #[tokio::test]
+    async fn auto_heartbeat_mode_answers() {
+        let mut server = MockServer::start().await;
+        let (mut client, mut receiver) = forwarding_client(HeartbeatMode::Auto);
+        client.connect(&server.url).await.unwrap();
+
+        server.push_text(HEARTBEAT);
+        let response = server.recv_request().await.unwrap();
+        assert_eq!(
+            response,
+            serde_json::json!({"method": "public/respond-heartbeat", "id": 42})
+        );
+        assert_eq!(client.heartbeat_count(), 1);
+        assert!(receiver.try_recv().is_err());
+
+        client.disconnect().await.unwrap();
+    }
+
+    /// Busy and overlapped flags of `slow_client`, and where the results go
+    type SlowState = (Arc<AtomicBool>, Arc<AtomicBool>, UnboundedSender<Forwarded>);
+
+    /// A client whose callback takes `delay` for every result, the flag is raised
+    /// if two calls ever run at the same time
+    fn slow_client(
+        delay: Duration,
+    ) -> (
+        CryptoClient<impl Future<Output = ()> + Send + Sync + 'static, SlowState>,
+        tokio::sync::mpsc::UnboundedReceiver<Forwarded>,
+        Arc<AtomicBool>,
+    ) {
+        let (sender, receiver) = unbounded_channel();
+        let overlapped = Arc::new(AtomicBool::new(false));
+        let state = (Arc::new(AtomicBool::new(false)), overlapped.clone(), sender);
+        let client = CryptoClientBuilder::new(
+            move |result: Forwarded, (busy, overlapped, sender): SlowState| async move {
+                if busy.swap(true, Ordering::SeqCst) {
+                    overlapped.store(true, Ordering::SeqCst);
+                }
+                tokio::time::sleep(delay).await;
+                busy.store(false, Ordering::SeqCst);
+                sender.send(result).ok();
+            },
+            state,
+        )
+        .with_ack_timeout(Duration::from_millis(100))
+        .build()
+        .unwrap();
+        (client, receiver, overlapped)
+    }
+
+    #[tokio::test]
+    async fn ack_timeout_does_not_overlap_a_slow_callback() {
+        let server = MockServer::start().await;
+        let (mut client, mut receiver, overlapped) = slow_client(Duration::from_millis(50));
+        client.connect(&server.url).await.unwrap();
+
+        client.subscribe_index("BTCUSD-INDEX").await.unwrap();
+        // Not an ack of the subscribe request
+        let trade = TRADE.replace("\"id\":1", "\"id\":-1");
+        for _ in 0..5 {
+            server.push_text(&trade);
+        }
+        // The timeout fires while the callback is busy with the trades
+        for _ in 0..5 {
+            assert!(matches!(
+                receiver.recv().await.unwrap(),
+                Ok(SubscribeResult::TradeResult(_))
+            ));
+        }
+        assert!(matches!(
+            receiver.recv().await.unwrap(),
+            Err(CryptoError::AckTimeout { id: 1, .. })
+        ));
+        assert!(!overlapped.load(Ordering::SeqCst));
+        client.disconnect_fast().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn ack_timeout_is_delivered_after_the_frames_read_before() {
+        let server = MockServer::start().await;
+        let (mut client, mut receiver, _) = slow_client(Duration::from_millis(200));
+        client.connect(&server.url).await.unwrap();
+
+        client.subscribe_index("BTCUSD-INDEX").await.unwrap();
+        let trade = TRADE.replace("\"id\":1", "\"id\":-1");
+        server.push_text(&trade);
+        server.push_text(&trade);
+        for _ in 0..2 {
+            assert!(matches!(
+                receiver.recv().await.unwrap(),
+                Ok(SubscribeResult::TradeResult(_))
+            ));
+        }
+        assert!(matches!(
+            receiver.recv().await.unwrap(),
+            Err(CryptoError::AckTimeout { id: 1, .. })
+        ));
+
+        // Only once
+        tokio::time::sleep(Duration::from_millis(500)).await;
+        assert!(receiver.try_recv().is_err());
+        client.disconnect_fast().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn slow_callback_does_not_delay_heartbeats() {
+        let mut server = MockServer::start().await;
+        let (sender, mut receiver) = unbounded_channel();
+        let mut client = CryptoClientBuilder::new(
+            |result: Forwarded, sender: UnboundedSender<Forwarded>| async move {
+                tokio::time::sleep(Duration::from_secs(2)).await;
+                sender.send(result).ok();
+            },
+            sender,
+        )
+        .build()
+        .unwrap();
+        client.connect(&server.url).await.unwrap();
+
+        server.push_text(TRADE);
+        server.push_text(HEARTBEAT);
+        server.push(Message::Ping(b"alive".to_vec()));
+        let response = server
+            .recv_timeout(Duration::from_millis(500))
+            .await
+            .expect("heartbeat not answered while the callback sleeps");
+        let Message::Text(response) = response else {
+            panic!("unexpected {response:?}");
+        };
+        assert_eq!(
+            serde_json::from_str::<Value>(&response).unwrap(),
+            serde_json::json!({"method": "public/respond-heartbeat", "id": 42})
+        );
+        assert!(matches!(
+            server.recv_timeout(Duration::from_millis(500)).await,
+            Some(Message::Pong(payload)) if payload == b"alive"
+        ));
+
+        // The result is still delivered once the callback is done
+        assert!(receiver.try_recv().is_err());
+        assert!(matches!(
+            receiver.recv().await.unwrap(),
+            Ok(SubscribeResult::TradeResult(_))
+        ));
+        client.disconnect_fast().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn manual_heartbeat_mode_delivers_the_request() {
+        let mut server = MockServer::start().await;
+        let (mut client, mut receiver) = forwarding_client(HeartbeatMode::Manual);
+        client.connect(&server.url).await.unwrap();
+
+        server.push_text(HEARTBEAT);
+        assert!(matches!(
+            receiver.recv().await.unwrap(),
+            Ok(SubscribeResult::HeartbeatRequest { id: 42 })
+        ));
+        assert_eq!(client.heartbeat_count(), 1);
+        assert!(server
+            .recv_timeout(Duration::from_millis(200))
+            .await
+            .is_none());
+
+        client.respond_heartbeat(42).await.unwrap();
+        let response = server.recv_request().await.unwrap();
+        assert_eq!(
+            response,
+            serde_json::json!({"method": "public/respond-heartbeat", "id": 42})
+        );
+
+        client.disconnect().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn disabled_heartbeat_mode_gets_disconnected() {
+        let mut server = MockServer::start().await;
+        let (mut client, mut receiver) = forwarding_client(HeartbeatMode::Disabled);
+        client.connect(&server.url).await.unwrap();
+
+        // Once the trade is delivered the reader is done with the heartbeat, an answer
+        // would already be on its way
+        server.push_text(HEARTBEAT);
+        server.push_text(TRADE);
+        assert!(matches!(
+            receiver.recv().await.unwrap(),
+            Ok(SubscribeResult::TradeResult(_))
+        ));
+        assert_eq!(client.heartbeat_count(), 1);
+        assert_eq!(
+            server.recv_timeout(Duration::from_millis(200)).await,
+            None,
+            "nothing is sent, not even a public/respond-heartbeat"
+        );
+
+        // The exchange gives up on the connection
+        server.push(Message::Close(None));
+        assert!(matches!(
+            receiver.recv().await.unwrap(),
+            Err(CryptoError::CloseError { .. })
+        ));
+        assert!(matches!(
+            client.wait().await,
+            Err(CryptoError::CloseError { .. })
+        ));
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn deprecated_setters_still_apply() {
+        let client = silent_client()
+            .with_market_url("ws://localhost/market".to_owned())
+            .with_user_url("ws://localhost/user".to_owned())
+            .with_request_timeout(Duration::from_secs(1))
+            .with_heartbeat_mode(HeartbeatMode::Disabled);
+        assert_eq!(client.config().market_url, "ws://localhost/market");
+        assert_eq!(client.config().user_url, "ws://localhost/user");
+        assert_eq!(client.config().request_timeout, Duration::from_secs(1));
+        assert_eq!(client.config().heartbeat_mode, HeartbeatMode::Disabled);
+    }
+
+    fn fast_backoff(max_attempts: Option<u32>) -> BackoffPolicy {
+        BackoffPolicy {
+            initial: Duration::from_millis(10),
+            max: Duration::from_millis(50),
+            multiplier: 2.0,
+            max_attempts,
+        }
+    }
+
+    #[tokio::test]
+    async fn reconnects_and_restores_the_subscriptions() {
+        let mut server = MockServer::start().await;
+        let (builder, mut receiver) = forwarding_builder();
+        let mut client = builder
+            .with_backoff_policy(fast_backoff(None))
+            .build()
+            .unwrap();
+        client.connect(&server.url).await.unwrap();
+
+        client.subscribe_index("BTCUSD-INDEX").await.unwrap();
+        client.subscribe_mark_price("BTCUSD-PERP").await.unwrap();
+        client
+            .unsubscribe(vec![model::mark("BTCUSD-PERP")])
+            .await
+            .unwrap();
+        for _ in 0..3 {
+            server.recv_request().await.unwrap();
+        }
+
+        server.push(Message::Close(None));
+        assert!(matches!(
+            receiver.recv().await.unwrap(),
+            Err(CryptoError::CloseError { .. })
+        ));
+        assert!(matches!(
+            receiver.recv().await.unwrap(),
+            Ok(SubscribeResult::Reconnected { attempt: 1 })
+        ));
+
+        let request = server.recv_request().await.unwrap();
+        assert_eq!(request["method"], "subscribe");
+        assert_eq!(
+            request["params"]["channels"],
+            serde_json::json!(["index.BTCUSD-INDEX"])
+        );
+
+        // The new connection is usable
+        server.push_text(TRADE);
+        assert!(matches!(
+            receiver.recv().await.unwrap(),
+            Ok(SubscribeResult::TradeResult(_))
+        ));
+        client.disconnect().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn reconnection_authenticates_before_subscribing() {
+        let mut server = MockServer::with_responder(user_response).await;
+        let (builder, mut receiver) = forwarding_builder();
+        let mut client = builder
+            .with_user_url(server.url.clone())
+            .with_credentials("key", "secret")
+            .with_backoff_policy(fast_backoff(None))
+            .build()
+            .unwrap();
+
+        client.connect_user().await.unwrap();
+        let auth = server.recv_request().await.unwrap();
+        assert_eq!(auth["method"], "public/auth");
+        assert_eq!(auth["api_key"], "key");
+        assert!(matches!(
+            receiver.recv().await.unwrap(),
+            Ok(SubscribeResult::AuthResult { success: true })
+        ));
+        client.subscribe_positions().await.unwrap();
+        server.recv_request().await.unwrap();
+
+        server.push(Message::Close(None));
+        let auth = server.recv_request().await.unwrap();
+        assert_eq!(auth["method"], "public/auth");
+        let request = server.recv_request().await.unwrap();
+        assert_eq!(request["method"], "subscribe");
+        assert_eq!(
+            request["params"]["channels"],
+            serde_json::json!(["user.positions"])
+        );
+        assert!(client.is_authenticated());
+
+        client.disconnect().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn session_is_restored_without_waiting_for_the_rate_limit() {
+        let mut server = MockServer::with_responder(user_response).await;
+        let (builder, mut receiver) = forwarding_builder();
+        let mut client = builder
+            .with_user_url(server.url.clone())
+            .with_credentials("key", "secret")
+            .with_backoff_policy(fast_backoff(None))
+            .with_rate_limit(RateLimit::new(2, Duration::from_secs(10)))
+            .build()
+            .unwrap();
+
+        // The auth and subscribe requests fill the window
+        client.connect_user().await.unwrap();
+        server.recv_request().await.unwrap();
+        assert!(matches!(
+            receiver.recv().await.unwrap(),
+            Ok(SubscribeResult::AuthResult { success: true })
+        ));
+        client.subscribe_positions().await.unwrap();
+        server.recv_request().await.unwrap();
+
+        server.push(Message::Close(None));
+        for method in ["public/auth", "subscribe"] {
+            let request = tokio::time::timeout(Duration::from_secs(2), server.recv_request())
+                .await
+                .expect("the reader waited for the rate limit")
+                .unwrap();
+            assert_eq!(request["method"], method);
+        }
+
+        client.disconnect_fast().await.unwrap();
+    }
+
+    const UNAUTHORIZED: &str = "{\"id\":3,\"method\":\"subscribe\",\"code\":10002,\"message\":\"UNAUTHORIZED\",\"channel\":\"user.order.ETH_CRO\"}";
+
+    #[tokio::test]
+    async fn expired_session_is_authenticated_again() {
+        let mut server = MockServer::with_responder(user_response).await;
+        let (builder, mut receiver) = forwarding_builder();
+        let mut client = builder
+            .with_user_url(server.url.clone())
+            .with_credentials("key", "secret")
+            .build()
+            .unwrap();
+        client.connect_user().await.unwrap();
+        server.recv_request().await.unwrap();
+        assert!(matches!(
+            receiver.recv().await.unwrap(),
+            Ok(SubscribeResult::AuthResult { success: true })
+        ));
+        client.subscribe_user_order("ETH_CRO").await.unwrap();
+        client.subscribe_positions().await.unwrap();
+        for _ in 0..2 {
+            server.recv_request().await.unwrap();
+        }
+
+        server.push_text(UNAUTHORIZED);
+        assert!(matches!(
+            receiver.recv().await.unwrap(),
+            Err(CryptoError::SubscriptionError { code: 10002, .. })
+        ));
+        let auth = server.recv_request().await.unwrap();
+        assert_eq!(auth["method"], "public/auth");
+        assert_eq!(auth["api_key"], "key");
+        let request = server.recv_request().await.unwrap();
+        assert_eq!(request["method"], "subscribe");
+        assert_eq!(
+            request["params"]["channels"],
+            serde_json::json!(["user.order.ETH_CRO", "user.positions"])
+        );
+        assert!(matches!(
+            receiver.recv().await.unwrap(),
+            Ok(SubscribeResult::AuthResult { success: true })
+        ));
+        assert!(matches!(
+            receiver.recv().await.unwrap(),
+            Ok(SubscribeResult::Reauthenticated { attempt: 1 })
+        ));
+        assert!(client.is_authenticated());
+        client.disconnect_fast().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn revoked_key_stops_authenticating() {
+        // Only the first auth request succeeds
+        let auths = Arc::new(AtomicU64::new(0));
+        let mut server = MockServer::with_responder(move |request| {
+            if request["method"] != "public/auth" {
+                return Vec::new();
+            }
+            let code = match auths.fetch_add(1, Ordering::SeqCst) {
+                0 => 0,
+                _ => 40101,
+            };
+            vec![Message::text(
+                serde_json::json!({"id": request["id"], "method": "public/auth", "code": code})
+                    .to_string(),
+            )]
+        })
+        .await;
+        let (builder, mut receiver) = forwarding_builder();
+        let mut client = builder
+            .with_user_url(server.url.clone())
+            .with_credentials("key", "secret")
+            .with_max_reauth_attempts(2)
+            .with_backoff_policy(fast_backoff(None))
+            .build()
+            .unwrap();
+        client.connect_user().await.unwrap();
+        server.recv_request().await.unwrap();
+        assert!(matches!(
+            receiver.recv().await.unwrap(),
+            Ok(SubscribeResult::AuthResult { success: true })
+        ));
+
+        server.push_text(UNAUTHORIZED);
+        assert!(matches!(
+            receiver.recv().await.unwrap(),
+            Err(CryptoError::SubscriptionError { code: 10002, .. })
+        ));
+        for _ in 0..2 {
+            assert_eq!(
+                server.recv_request().await.unwrap()["method"],
+                "public/auth"
+            );
+            assert!(matches!(
+                receiver.recv().await.unwrap(),
+                Ok(SubscribeResult::AuthResult { success: false })
+            ));
+        }
+        assert!(matches!(
+            receiver.recv().await.unwrap(),
+            Err(CryptoError::AuthenticationFailed { attempts: 2 })
+        ));
+        assert!(matches!(server.recv().await, Some(Message::Close(_))));
+
+        // No reconnection with a revoked key
+        assert!(matches!(
+            client.wait().await,
+            Err(CryptoError::AuthenticationFailed { attempts: 2 })
+        ));
+        assert!(!client.is_authenticated());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn maintenance_close_reconnects_slowly() {
+        let mut server = MockServer::with_responder(|request| {
+            let response =
+                serde_json::json!({"id": request["id"], "method": "subscribe", "code": 0});
+            vec![Message::text(response.to_string())]
+        })
+        .await;
+        let (builder, mut receiver) = forwarding_builder();
+        let mut client = builder
+            .with_backoff_policy(fast_backoff(None))
+            .build()
+            .unwrap();
+        client.connect(&server.url).await.unwrap();
+        client.subscribe_index("BTCUSD-INDEX").await.unwrap();
+        server.recv_request().await.unwrap();
+
+        server.push(Message::Close(Some(CloseFrame {
+            code: CloseCode::Restart,
+            reason: "Back at 03:00 UTC".into(),
+        })));
+        let start = tokio::time::Instant::now();
+        assert!(matches!(
+            receiver.recv().await.unwrap(),
+            Err(CryptoError::Maintenance { resume_hint: Some(hint) }) if hint == "Back at 03:00 UTC"
+        ));
+        tokio::time::sleep(Duration::from_secs(1)).await;
+        assert_eq!(client.health().state, ConnectionState::Maintenance);
+
+        // The maintenance backoff starts at 30 seconds instead of 10 milliseconds
+        assert!(matches!(
+            receiver.recv().await.unwrap(),
+            Ok(SubscribeResult::Reconnected { attempt: 1 })
+        ));
+        assert!(start.elapsed() >= Duration::from_secs(30));
+        assert!(matches!(
+            receiver.recv().await.unwrap(),
+            Ok(SubscribeResult::MaintenanceEnded { attempt: 1 })
+        ));
+        let request = server.recv_request().await.unwrap();
+        assert_eq!(
+            request["params"]["channels"],
+            serde_json::json!(["index.BTCUSD-INDEX"])
+        );
+
+        // The usual backoff is back for the next close
+        server.push(Message::Close(None));
+        assert!(matches!(
+            receiver.recv().await.unwrap(),
+            Err(CryptoError::CloseError { .. })
+        ));
+        let start = tokio::time::Instant::now();
+        assert!(matches!(
+            receiver.recv().await.unwrap(),
+            Ok(SubscribeResult::Reconnected { attempt: 1 })
+        ));
+        assert!(start.elapsed() < Duration::from_secs(1));
+        client.disconnect_fast().await.unwrap();
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn maintenance_notice_is_not_a_parse_error() {
+        let server = MockServer::start().await;
+        let (builder, mut receiver) = forwarding_builder();
+        let mut client = builder
+            .with_backoff_policy(fast_backoff(None))
+            .with_maintenance_backoff(BackoffPolicy {
+                initial: Duration::from_secs(5),
+                max: Duration::from_secs(20),
+                multiplier: 2.0,
+                max_attempts: None,
+            })
+            .with_parse_failure_policy(ParseFailurePolicy::Disconnect)
+            .build()
+            .unwrap();
+        client.connect(&server.url).await.unwrap();
+
+        server.push_text(
+            "{\"id\":-1,\"method\":\"public/notice\",\"code\":0,\"message\":\"Scheduled maintenance in 5 minutes\"}",
+        );
+        assert!(matches!(
+            receiver.recv().await.unwrap(),
+            Err(CryptoError::Maintenance { resume_hint: Some(hint) })
+                if hint == "Scheduled maintenance in 5 minutes"
+        ));
+        // Still connected, the parse failure policy does not apply
+        server.push_text(TRADE);
+        assert!(matches!(
+            receiver.recv().await.unwrap(),
+            Ok(SubscribeResult::TradeResult(_))
+        ));
+
+        // The close that follows is expected, it is not delivered
+        server.push(Message::Close(None));
+        let start = tokio::time::Instant::now();
+        assert!(matches!(
+            receiver.recv().await.unwrap(),
+            Ok(SubscribeResult::Reconnected { attempt: 1 })
+        ));
+        assert!(start.elapsed() >= Duration::from_secs(5));
+        assert!(matches!(
+            receiver.recv().await.unwrap(),
+            Ok(SubscribeResult::MaintenanceEnded { attempt: 1 })
+        ));
+        client.disconnect_fast().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn reconnection_gives_up_after_max_attempts() {
+        // A server that closes the only connection it accepts and then goes away
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("ws://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut ws = tokio_tungstenite::accept_async(stream).await.unwrap();
+            ws.send(Message::Close(None)).await.ok();
+        });
+
+        let (builder, mut receiver) = forwarding_builder();
+        let mut client = builder
+            .with_backoff_policy(fast_backoff(Some(2)))
+            .build()
+            .unwrap();
+        client.connect(&url).await.unwrap();
+
+        assert!(matches!(
+            receiver.recv().await.unwrap(),
+            Err(CryptoError::CloseError { .. })
+        ));
+        assert!(receiver.recv().await.unwrap().is_err());
+        assert!(receiver.recv().await.unwrap().is_err());
+        assert!(matches!(
+            receiver.recv().await.unwrap(),
+            Err(CryptoError::ReconnectFailed { attempts: 2 })
+        ));
+    }
+
+    #[tokio::test]
+    async fn silent_connection_times_out() {
+        let server = MockServer::start().await;
+        let (builder, mut receiver) = forwarding_builder();
+        let mut client = builder
+            .with_heartbeat_timeout(Duration::from_millis(100))
+            .build()
+            .unwrap();
+        client.connect(&server.url).await.unwrap();
+
+        server.push_text(HEARTBEAT);
+        assert!(matches!(
+            receiver.recv().await.unwrap(),
+            Err(CryptoError::HeartbeatTimeout { .. })
+        ));
+        assert_eq!(client.heartbeat_count(), 1);
+    }
+
+    #[tokio::test]
+    async fn pings_are_sent_every_interval() {
+        let mut server = MockServer::start().await;
+        let (builder, _receiver) = forwarding_builder();
+        let mut client = builder
+            .with_ping_interval(Duration::from_millis(50))
+            .build()
+            .unwrap();
+        client.connect(&server.url).await.unwrap();
+
+        for _ in 0..2 {
+            assert!(matches!(server.recv().await, Some(Message::Ping(_))));
+        }
+        client.disconnect().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn rate_limit_delays_the_requests() {
+        let mut server = MockServer::start().await;
+        let (builder, _receiver) = forwarding_builder();
+        let mut client = builder
+            .with_rate_limit(RateLimit::new(2, Duration::from_millis(300)))
+            .build()
+            .unwrap();
+        client.connect(&server.url).await.unwrap();
+
+        let start = std::time::Instant::now();
+        client.subscribe_index("BTCUSD-INDEX").await.unwrap();
+        client.subscribe_index("ETHUSD-INDEX").await.unwrap();
+        assert!(start.elapsed() < Duration::from_millis(300));
+        client.subscribe_index("CROUSD-INDEX").await.unwrap();
+        assert!(start.elapsed() >= Duration::from_millis(300));
+        for _ in 0..3 {
+            server.recv_request().await.unwrap();
+        }
+
+        // Heartbeats are answered right away
+        server.push_text(HEARTBEAT);
+        let response = server.recv_request().await.unwrap();
+        assert_eq!(response["method"], "public/respond-heartbeat");
+        client.disconnect().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn connect_times_out() {
+        // Accepts the tcp connection but never answers the handshake
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("ws://{}", listener.local_addr().unwrap());
+
+        let (builder, _receiver) = forwarding_builder();
+        let mut client = builder
+            .with_connect_timeout(Duration::from_millis(100))
+            .build()
+            .unwrap();
+        assert!(matches!(
+            client.connect(&url).await,
+            Err(CryptoError::ConnectTimeout { .. })
+        ));
+        drop(listener);
+    }
+
+    #[tokio::test]
+    async fn connect_user_authenticates_with_the_credentials() {
+        let mut server = MockServer::with_responder(user_response).await;
+        let (builder, mut receiver) = forwarding_builder();
+        let mut client = builder
+            .with_user_url(server.url.clone())
+            .with_credentials("key", "secret")
+            .build()
+            .unwrap();
+
+        client.connect_user().await.unwrap();
+        let auth = server.recv_request().await.unwrap();
+        assert_eq!(auth["method"], "public/auth");
+        assert_eq!(auth["api_key"], "key");
+        assert_eq!(auth["sig"].as_str().unwrap().len(), 64);
+        assert!(matches!(
+            receiver.recv().await.unwrap(),
+            Ok(SubscribeResult::AuthResult { success: true })
+        ));
+        assert!(client.is_authenticated());
+        client.disconnect().await.unwrap();
+
+        // Without credentials nothing is sent
+        let mut server = MockServer::start().await;
+        let (builder, _receiver) = forwarding_builder();
+        let mut client = builder.with_user_url(server.url.clone()).build().unwrap();
+        client.connect_user().await.unwrap();
+        assert!(server
+            .recv_timeout(Duration::from_millis(200))
+            .await
+            .is_none());
+        assert!(!client.is_authenticated());
+        client.disconnect().await.unwrap();
+    }
+
+    fn unsubscribe_response(request: &Value) -> Vec<Message> {
+        if request["method"] != "unsubscribe" {
+            return Vec::new();
+        }
+        let response = serde_json::json!({"id": request["id"], "method": "unsubscribe", "code": 0});
+        vec![Message::text(response.to_string())]
+    }
+
+    #[tokio::test]
+    async fn disconnect_unsubscribes_before_closing() {
+        let mut server = MockServer::with_responder(unsubscribe_response).await;
+        let (builder, mut receiver) = forwarding_builder();
+        let mut client = builder.build().unwrap();
+        client.connect(&server.url).await.unwrap();
+        client.subscribe_mark_price("BTCUSD-PERP").await.unwrap();
+        client.subscribe_index("BTCUSD-INDEX").await.unwrap();
+
+        client.disconnect().await.unwrap();
+        assert!(matches!(
+            receiver.recv().await.unwrap(),
+            Ok(SubscribeResult::UnsubscriptionResult { success: true })
+        ));
+
+        for _ in 0..2 {
+            assert_eq!(server.recv_request().await.unwrap()["method"], "subscribe");
+        }
+        let request = server.recv_request().await.unwrap();
+        assert_eq!(request["method"], "unsubscribe");
+        assert_eq!(
+            request["params"]["channels"],
+            serde_json::json!(["index.BTCUSD-INDEX", "mark.BTCUSD-PERP"])
+        );
+        assert!(matches!(server.recv().await, Some(Message::Close(_))));
+    }
+
+    #[tokio::test]
+    async fn disconnect_returns_the_reader_error() {
+        let server = MockServer::start().await;
+        let (builder, mut receiver) = forwarding_builder();
+        let mut client = builder.build().unwrap();
+        client.connect(&server.url).await.unwrap();
+
+        server.push(Message::Close(None));
+        assert!(matches!(
+            receiver.recv().await.unwrap(),
+            Err(CryptoError::CloseError { .. })
+        ));
+        assert!(matches!(
+            client.disconnect().await,
+            Err(CryptoError::CloseError { .. })
+        ));
+    }
+
+    /// Accepts one websocket and resets its tcp connection, without a close frame
+    async fn dropping_server() -> (String, JoinHandle<()>) {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("ws://{}", listener.local_addr().unwrap());
+        let server = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let ws = tokio_tungstenite::accept_async(stream).await.unwrap();
+            ws.get_ref().set_zero_linger().unwrap();
+        });
+        (url, server)
+    }
+
+    #[tokio::test]
+    async fn disconnect_after_the_connection_was_dropped() {
+        for fast in [false, true] {
+            let (url, server) = dropping_server().await;
+            let (builder, mut receiver) = forwarding_builder();
+            let mut client = builder.build().unwrap();
+            client.connect(&url).await.unwrap();
+            server.await.unwrap();
+            assert!(matches!(
+                receiver.recv().await.unwrap(),
+                Err(CryptoError::TungsteniteErrorString(_))
+            ));
+            while !client.reader_join.as_ref().unwrap().is_finished() {
+                tokio::task::yield_now().await;
+            }
+
+            // The close frame cannot be sent, the reader error is still returned
+            let disconnect = async {
+                if fast {
+                    client.disconnect_fast().await
+                } else {
+                    client.disconnect().await
+                }
+            };
+            let result = tokio::time::timeout(Duration::from_secs(5), disconnect)
+                .await
+                .unwrap();
+            assert!(
+                matches!(result, Err(CryptoError::TungsteniteError(_))),
+                "{result:?}"
+            );
+            assert!(client.reader_join.is_none());
+        }
+    }
+
+    #[tokio::test]
+    async fn disconnect_fast_does_not_unsubscribe() {
+        let mut server = MockServer::with_responder(unsubscribe_response).await;
+        let mut client = silent_client();
+        client.connect(&server.url).await.unwrap();
+        client.subscribe_index("BTCUSD-INDEX").await.unwrap();
+
+        client.disconnect_fast().await.unwrap();
+        assert_eq!(server.recv_request().await.unwrap()["method"], "subscribe");
+        assert!(matches!(server.recv().await, Some(Message::Close(_))));
+        assert!(matches!(
+            client.subscribe_index("BTCUSD-INDEX").await,
+            Err(CryptoError::NotConnectedError)
+        ));
+    }
+
+    #[tokio::test]
+    async fn cancellation_closes_the_connection() {
+        let mut server = MockServer::start().await;
+        let token = tokio_util::sync::CancellationToken::new();
+        let (builder, mut receiver) = forwarding_builder();
+        let mut client = builder
+            .with_cancellation_token(token.clone())
+            .with_ping_interval(Duration::from_millis(20))
+            .build()
+            .unwrap();
+        client.connect(&server.url).await.unwrap();
+
+        server.push_text(TRADE);
+        assert!(matches!(
+            receiver.recv().await.unwrap(),
+            Ok(SubscribeResult::TradeResult(_))
+        ));
+        token.cancel();
+        client.wait().await.unwrap();
+
+        // Pings until the close frame, nothing after it
+        loop {
+            match server.recv().await {
+                Some(Message::Ping(_)) => continue,
+                Some(Message::Close(_)) => break,
+                other => panic!("unexpected {other:?}"),
+            }
+        }
+        assert!(server
+            .recv_timeout(Duration::from_millis(100))
+            .await
+            .is_none());
+        assert!(receiver.try_recv().is_err());
+        assert!(matches!(
+            client.subscribe_index("BTCUSD-INDEX").await,
+            Err(CryptoError::NotConnectedError)
+        ));
+    }
+
+    #[tokio::test]
+    async fn cancellation_stops_reconnecting() {
+        let server = MockServer::start().await;
+        let token = tokio_util::sync::CancellationToken::new();
+        let (builder, mut receiver) = forwarding_builder();
+        let mut client = builder
+            .with_cancellation_token(token.clone())
+            .with_backoff_policy(BackoffPolicy {
+                initial: Duration::from_secs(60),
+                max: Duration::from_secs(60),
+                ..Default::default()
+            })
+            .build()
+            .unwrap();
+        client.connect(&server.url).await.unwrap();
+
+        server.push(Message::Close(None));
+        assert!(matches!(
+            receiver.recv().await.unwrap(),
+            Err(CryptoError::CloseError { .. })
+        ));
+        token.cancel();
+        tokio::time::timeout(Duration::from_secs(1), client.wait())
+            .await
+            .unwrap()
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn parse_failures_are_skipped_by_default() {
+        let mut server = MockServer::start().await;
+        let (builder, mut receiver) = forwarding_builder();
+        let mut client = builder.build().unwrap();
+        client.connect(&server.url).await.unwrap();
+
+        for _ in 0..3 {
+            server.push_text("{\"method\":\"subscribe\",");
+            assert!(matches!(
+                receiver.recv().await.unwrap(),
+                Err(CryptoError::SerdeError(_))
+            ));
+        }
+        server.push_text(TRADE);
+        assert!(matches!(
+            receiver.recv().await.unwrap(),
+            Ok(SubscribeResult::TradeResult(_))
+        ));
+        assert!(client.shared.is_connected().await);
+        assert!(server
+            .recv_timeout(Duration::from_millis(100))
+            .await
+            .is_none());
+        client.disconnect_fast().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn parse_failure_disconnects() {
+        let mut server = MockServer::start().await;
+        let (builder, mut receiver) = forwarding_builder();
+        let mut client = builder
+            .with_parse_failure_policy(ParseFailurePolicy::Disconnect)
+            .build()
+            .unwrap();
+        client.connect(&server.url).await.unwrap();
+
+        server.push_text("not json");
+        assert!(matches!(
+            receiver.recv().await.unwrap(),
+            Err(CryptoError::SerdeError(_))
+        ));
+        assert!(matches!(server.recv().await, Some(Message::Close(_))));
+        assert!(matches!(
+            client.wait().await,
+            Err(CryptoError::ParseFailures { failures: 1 })
+        ));
+        assert!(!client.shared.is_connected().await);
+    }
+
+    #[tokio::test]
+    async fn consecutive_parse_failures_reconnect() {
+        let mut server = MockServer::start().await;
+        let (builder, mut receiver) = forwarding_builder();
+        let mut client = builder
+            .with_parse_failure_policy(ParseFailurePolicy::DisconnectAfter(2))
+            .with_backoff_policy(fast_backoff(None))
+            .build()
+            .unwrap();
+        client.connect(&server.url).await.unwrap();
+        client.subscribe_index("BTCUSD-INDEX").await.unwrap();
+        server.recv_request().await.unwrap();
+
+        // A frame parsed in between resets the count
+        for frame in ["not json", TRADE, "not json"] {
+            server.push_text(frame);
+            receiver.recv().await.unwrap().ok();
+        }
+        assert!(server
+            .recv_timeout(Duration::from_millis(100))
+            .await
+            .is_none());
+
+        server.push_text("not json");
+        assert!(matches!(
+            receiver.recv().await.unwrap(),
+            Err(CryptoError::SerdeError(_))
+        ));
+        assert!(matches!(server.recv().await, Some(Message::Close(_))));
+        assert!(matches!(
+            receiver.recv().await.unwrap(),
+            Ok(SubscribeResult::Reconnected { attempt: 1 })
+        ));
+        // The new connection is the one receiving the pushed frames now
+        server.recv_request().await.unwrap();
+
+        // The count starts again on the new connection
+        server.push_text("not json");
+        assert!(matches!(
+            receiver.recv().await.unwrap(),
+            Err(CryptoError::SerdeError(_))
+        ));
+        server.push_text(TRADE);
+        assert!(matches!(
+            receiver.recv().await.unwrap(),
+            Ok(SubscribeResult::TradeResult(_))
+        ));
+        client.disconnect().await.unwrap();
+    }
+
+    const BOOK: &str = "{\"method\":\"subscribe\",\"id\":1,\"code\":0,\"result\":{\"channel\":\"book\",\"instrument_name\":\"ETH_CRO\",\"subscription\":\"book.ETH_CRO.10\",\"depth\":10,\"data\":[]}}";
+    const USER_ORDER: &str = "{\"method\":\"subscribe\",\"id\":1,\"code\":0,\"result\":{\"channel\":\"user.order\",\"instrument_name\":\"ETH_CRO\",\"subscription\":\"user.order.ETH_CRO\",\"data\":[]}}";
+
+    const ALL_TICKERS: &str = "{\"method\":\"subscribe\",\"id\":1,\"code\":0,\"result\":{\"channel\":\"ticker\",\"subscription\":\"ticker\",\"data\":[{\"i\":\"ETH_CRO\",\"h\":1,\"v\":10,\"a\":0.5,\"l\":0.01,\"b\":0.02,\"k\":0.6,\"c\":-0.4,\"t\":1587523078844},{\"i\":\"BTC_USDT\",\"h\":70000,\"v\":12.5,\"a\":68000,\"l\":65000,\"b\":67999,\"k\":68001,\"c\":0.02,\"t\":1587523078845},{\"i\":\"ETH_CRO\",\"h\":1,\"v\":11,\"a\":0.55,\"l\":0.01,\"b\":0.54,\"k\":0.56,\"c\":-0.3,\"t\":1587523078846}]}}";
+
+    #[tokio::test]
+    async fn all_tickers_are_routed_by_instrument() {
+        let mut server = MockServer::start().await;
+        let (builder, mut receiver) = forwarding_builder();
+        let mut client = builder.build().unwrap();
+        let (ticker_sender, mut tickers) = unbounded_channel();
+        client.on_channel(
+            Channel::Ticker {
+                instrument_name: "ETH_CRO".to_owned(),
+            },
+            move |envelope: Envelope| {
+                let ticker_sender = ticker_sender.clone();
+                async move {
+                    ticker_sender.send(envelope.result).ok();
+                }
+            },
+        );
+        client.connect(&server.url).await.unwrap();
+
+        client.subscribe_all_tickers().await.unwrap();
+        let request = server.recv_request().await.unwrap();
+        assert_eq!(request["params"]["channels"], serde_json::json!(["ticker"]));
+
+        server.push_text(ALL_TICKERS);
+        match tickers.recv().await.unwrap() {
+            Ok(SubscribeResult::TickerResult(result)) => {
+                assert_eq!(result.instrument_name, "ETH_CRO");
+                assert_eq!(result.subscription, "ticker.ETH_CRO");
+                let times: Vec<u64> = result.data.iter().map(|ticker| ticker.time).collect();
+                assert_eq!(times, vec![1587523078844, 1587523078846]);
+            }
+            other => panic!("unexpected {other:?}"),
+        }
+        // The other instruments go to the callback
+        match receiver.recv().await.unwrap() {
+            Ok(SubscribeResult::TickerResult(result)) => {
+                assert!(result.is_all_instruments());
+                let instruments: Vec<&str> = result.tickers().map(|(name, _)| name).collect();
+                assert_eq!(instruments, vec!["BTC_USDT"]);
+            }
+            other => panic!("unexpected {other:?}"),
+        }
+        client.disconnect_fast().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn channels_are_routed_to_their_handlers() {
+        let mut server = MockServer::start().await;
+        let (builder, mut receiver) = forwarding_builder();
+        let mut client = builder
+            .with_backoff_policy(fast_backoff(None))
+            .build()
+            .unwrap();
+
+        let book = Channel::Book {
+            instrument_name: "ETH_CRO".to_owned(),
+            depth: 10,
+        };
+        let (book_sender, mut books) = unbounded_channel();
+        client.on_channel(book.clone(), move |envelope: Envelope| {
+            let book_sender = book_sender.clone();
+            async move {
+                book_sender.send(envelope.result).ok();
+            }
+        });
+        client.connect(&server.url).await.unwrap();
+        client.subscribe_index("BTCUSD-INDEX").await.unwrap();
+        server.recv_request().await.unwrap();
+        let (user_sender, mut users) = unbounded_channel();
+        client.on_channel_prefix("user.", move |envelope: Envelope| {
+            let user_sender = user_sender.clone();
+            async move {
+                user_sender.send(envelope.result).ok();
+            }
+        });
+
+        for frame in [BOOK, USER_ORDER, TRADE] {
+            server.push_text(frame);
+        }
+        assert!(matches!(
+            books.recv().await.unwrap(),
+            Ok(SubscribeResult::BookResult(_))
+        ));
+        assert!(matches!(
+            users.recv().await.unwrap(),
+            Ok(SubscribeResult::OrderResult(_))
+        ));
+        assert!(matches!(
+            receiver.recv().await.unwrap(),
+            Ok(SubscribeResult::TradeResult(_))
+        ));
+
+        // The handlers are kept on the new connection
+        server.push(Message::Close(None));
+        assert!(matches!(
+            receiver.recv().await.unwrap(),
+            Err(CryptoError::CloseError { .. })
+        ));
+        assert!(matches!(
+            receiver.recv().await.unwrap(),
+            Ok(SubscribeResult::Reconnected { attempt: 1 })
+        ));
+        server.recv_request().await.unwrap();
+        server.push_text(BOOK);
+        assert!(matches!(
+            books.recv().await.unwrap(),
+            Ok(SubscribeResult::BookResult(_))
+        ));
+
+        // Without handler the book goes to the callback
+        assert!(client.off_channel(&book));
+        assert!(!client.off_channel(&book));
+        for frame in [BOOK, USER_ORDER] {
+            server.push_text(frame);
+        }
+        assert!(matches!(
+            receiver.recv().await.unwrap(),
+            Ok(SubscribeResult::BookResult(_))
+        ));
+        assert!(matches!(
+            users.recv().await.unwrap(),
+            Ok(SubscribeResult::OrderResult(_))
+        ));
+        assert!(books.try_recv().is_err());
+        assert!(receiver.try_recv().is_err());
+        client.disconnect_fast().await.unwrap();
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn unacknowledged_subscribe_times_out_once() {
+        let server = MockServer::start().await;
+        let (builder, mut receiver) = forwarding_builder();
+        let mut client = builder.build().unwrap();
+        client.connect(&server.url).await.unwrap();
+
+        let start = tokio::time::Instant::now();
+        client.subscribe_index("BTCUSD-INDEX").await.unwrap();
+        match receiver.recv().await.unwrap() {
+            Err(CryptoError::AckTimeout { id, channels }) => {
+                assert_eq!(id, 1);
+                assert_eq!(channels, vec!["index.BTCUSD-INDEX".to_owned()]);
+            }
+            other => panic!("unexpected {other:?}"),
+        }
+        assert!(start.elapsed() >= Duration::from_secs(10));
+
+        // A late error ack is not delivered again
+        server.push_text(
+            "{\"id\":1,\"method\":\"subscribe\",\"code\":10004,\"message\":\"BAD_REQUEST\"}",
+        );
+        server.push_text(TRADE);
+        assert!(matches!(
+            receiver.recv().await.unwrap(),
+            Ok(SubscribeResult::TradeResult(_))
+        ));
+        tokio::time::sleep(Duration::from_secs(60)).await;
+        assert!(receiver.try_recv().is_err());
+
+        client.disconnect_fast().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn acknowledged_subscribe_does_not_time_out() {
+        let server = MockServer::with_responder(|request| {
+            let response =
+                serde_json::json!({"id": request["id"], "method": "subscribe", "code": 0});
+            vec![Message::text(response.to_string())]
+        })
+        .await;
+        let (builder, mut receiver) = forwarding_builder();
+        let mut client = builder
+            .with_ack_timeout(Duration::from_millis(100))
+            .build()
+            .unwrap();
+        client.connect(&server.url).await.unwrap();
+
+        client.subscribe_index("BTCUSD-INDEX").await.unwrap();
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        assert!(receiver.try_recv().is_err());
+
+        client.disconnect_fast().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn health_follows_the_connection() {
+        let mut server = MockServer::start().await;
+        let mut client = silent_client();
+        assert_eq!(client.health().state, ConnectionState::Disconnected);
+
+        client.connect(&server.url).await.unwrap();
+        let health = client.health();
+        assert_eq!(health.state, ConnectionState::Connected);
+        assert_eq!(health.since_last_frame, None);
+        assert_eq!(health.since_last_heartbeat, None);
+        assert_eq!(health.heartbeats_answered, 0);
+        assert_eq!(health.reconnect_attempt, 0);
+        assert_eq!(health.ping_rtt, None);
+
+        server.push_text(HEARTBEAT);
+        server.recv_request().await.unwrap();
+        while client.health().heartbeats_answered == 0 {
+            tokio::task::yield_now().await;
+        }
+        let health = client.health();
+        assert_eq!(health.heartbeats_answered, 1);
+        assert!(health.since_last_frame.is_some());
+        assert!(health.since_last_heartbeat.is_some());
+
+        client.disconnect().await.unwrap();
+        assert_eq!(client.health().state, ConnectionState::Disconnected);
+    }
+
+    #[tokio::test]
+    async fn health_measures_the_ping_round_trip() {
+        let server = MockServer::start().await;
+        let (builder, _receiver) = forwarding_builder();
+        let mut client = builder
+            .with_ping_interval(Duration::from_millis(20))
+            .build()
+            .unwrap();
+        client.connect(&server.url).await.unwrap();
+
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while client.health().ping_rtt.is_none() {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .unwrap();
+        client.disconnect().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn health_reports_the_reconnection_attempt() {
+        let server = MockServer::start().await;
+        let (builder, mut receiver) = forwarding_builder();
+        let mut client = builder
+            .with_backoff_policy(BackoffPolicy {
+                initial: Duration::from_millis(300),
+                max: Duration::from_millis(300),
+                ..Default::default()
+            })
+            .build()
+            .unwrap();
+        client.connect(&server.url).await.unwrap();
+
+        server.push(Message::Close(None));
+        assert!(matches!(
+            receiver.recv().await.unwrap(),
+            Err(CryptoError::CloseError { .. })
+        ));
+        // The reader marks the attempt right after delivering the error
+        while client.health().state != ConnectionState::Connecting {
+            tokio::task::yield_now().await;
+        }
+        assert_eq!(client.health().reconnect_attempt, 1);
+
+        assert!(matches!(
+            receiver.recv().await.unwrap(),
+            Ok(SubscribeResult::Reconnected { attempt: 1 })
+        ));
+        let health = client.health();
+        assert_eq!(health.state, ConnectionState::Connected);
+        assert_eq!(health.reconnect_attempt, 0);
+        client.disconnect().await.unwrap();
     }
 }
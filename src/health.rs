@@ -0,0 +1,159 @@
+use std::sync::atomic::{AtomicU32, AtomicU64, AtomicU8, Ordering};
+use std::sync::RwLock;
+use std::time::{Duration, Instant};
+
+/// State of the websocket of a client
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConnectionState {
+    Disconnected,
+    /// Opening the websocket, the first time or after losing it
+    Connecting,
+    Connected,
+    /// Waiting for the end of a maintenance of the exchange before reconnecting
+    Maintenance,
+}
+
+impl ConnectionState {
+    fn from_u8(value: u8) -> ConnectionState {
+        match value {
+            1 => ConnectionState::Connecting,
+            2 => ConnectionState::Connected,
+            3 => ConnectionState::Maintenance,
+            _ => ConnectionState::Disconnected,
+        }
+    }
+
+    fn as_u8(self) -> u8 {
+        match self {
+            ConnectionState::Disconnected => 0,
+            ConnectionState::Connecting => 1,
+            ConnectionState::Connected => 2,
+            ConnectionState::Maintenance => 3,
+        }
+    }
+}
+
+/// Snapshot of the health of a connection, see `CryptoClient::health`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Health {
+    pub state: ConnectionState,
+
+    /// Time since the last frame was received, `None` before the first one
+    pub since_last_frame: Option<Duration>,
+
+    /// Time since the last heartbeat request of the exchange, `None` before the first one
+    pub since_last_heartbeat: Option<Duration>,
+
+    /// Heartbeats answered since the client was created
+    pub heartbeats_answered: u64,
+
+    /// Current reconnection attempt, 0 when not reconnecting
+    pub reconnect_attempt: u32,
+
+    /// Smoothed round trip time of the keepalive pings, `None` without ping interval
+    /// or before the first pong
+    pub ping_rtt: Option<Duration>,
+}
+
+/// Weight of a new round trip sample in the smoothed value
+const RTT_WEIGHT: f64 = 0.2;
+
+/// Updated by the reader and the ping task, read by `CryptoClient::health`
+#[derive(Default)]
+pub(crate) struct HealthTracker {
+    state: AtomicU8,
+    last_frame: RwLock<Option<Instant>>,
+    last_heartbeat: RwLock<Option<Instant>>,
+    heartbeats_answered: AtomicU64,
+    reconnect_attempt: AtomicU32,
+    ping_sent: RwLock<Option<Instant>>,
+    ping_rtt: RwLock<Option<Duration>>,
+}
+
+impl HealthTracker {
+    pub(crate) fn set_state(&self, state: ConnectionState) {
+        self.state.store(state.as_u8(), Ordering::SeqCst);
+    }
+
+    pub(crate) fn set_reconnect_attempt(&self, attempt: u32) {
+        self.reconnect_attempt.store(attempt, Ordering::SeqCst);
+    }
+
+    pub(crate) fn frame_received(&self, at: Instant) {
+        *self.last_frame.write().unwrap() = Some(at);
+    }
+
+    pub(crate) fn heartbeat_received(&self, at: Instant) {
+        *self.last_heartbeat.write().unwrap() = Some(at);
+    }
+
+    pub(crate) fn heartbeat_answered(&self) {
+        self.heartbeats_answered.fetch_add(1, Ordering::SeqCst);
+    }
+
+    pub(crate) fn ping_sent(&self, at: Instant) {
+        *self.ping_sent.write().unwrap() = Some(at);
+    }
+
+    /// Update the round trip time with the pong of the last ping
+    pub(crate) fn pong_received(&self, at: Instant) {
+        let Some(sent) = self.ping_sent.write().unwrap().take() else {
+            return;
+        };
+        let sample = at.saturating_duration_since(sent);
+        let mut rtt = self.ping_rtt.write().unwrap();
+        *rtt = Some(match *rtt {
+            Some(rtt) => rtt.mul_f64(1.0 - RTT_WEIGHT) + sample.mul_f64(RTT_WEIGHT),
+            None => sample,
+        });
+    }
+
+    pub(crate) fn snapshot(&self) -> Health {
+        let since = |at: &RwLock<Option<Instant>>| at.read().unwrap().map(|at| at.elapsed());
+        Health {
+            state: ConnectionState::from_u8(self.state.load(Ordering::SeqCst)),
+            since_last_frame: since(&self.last_frame),
+            since_last_heartbeat: since(&self.last_heartbeat),
+            heartbeats_answered: self.heartbeats_answered.load(Ordering::SeqCst),
+            reconnect_attempt: self.reconnect_attempt.load(Ordering::SeqCst),
+            ping_rtt: *self.ping_rtt.read().unwrap(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn starts_disconnected() {
+        let health = HealthTracker::default().snapshot();
+        assert_eq!(health.state, ConnectionState::Disconnected);
+        assert_eq!(health.since_last_frame, None);
+        assert_eq!(health.ping_rtt, None);
+    }
+
+    #[test]
+    fn ping_rtt_is_smoothed() {
+        let tracker = HealthTracker::default();
+        let start = Instant::now();
+
+        // A pong without ping is ignored
+        tracker.pong_received(start);
+        assert_eq!(tracker.snapshot().ping_rtt, None);
+
+        tracker.ping_sent(start);
+        tracker.pong_received(start + Duration::from_millis(100));
+        assert_eq!(
+            tracker.snapshot().ping_rtt,
+            Some(Duration::from_millis(100))
+        );
+
+        tracker.ping_sent(start);
+        tracker.pong_received(start + Duration::from_millis(200));
+        assert_eq!(
+            tracker.snapshot().ping_rtt,
+            Some(Duration::from_millis(120))
+        );
+    }
+}
@@ -1,11 +1,21 @@
 mod model;
 mod client;
+mod builder;
+mod reader;
+mod health;
+mod router;
 mod message;
 mod subscription;
+#[cfg(test)]
+mod mock;
 
-pub use model::{Book, BookResult, CandlestickResult, Candlestick, TickerResult, Ticker, Trade, TradeResult, candlestick, TimeFrame, trade, ticker, book, Side, balance, BalanceResult, PositionBalance, Balance};
-pub use client::{CryptoClient, CryptoError};
-pub use message::SubscribeResult;
+pub use model::{Book, BookResult, BookSnapshotResult, Offer, OrderBook, BookSide, CandlestickResult, Candlestick, TickerResult, Ticker, Trade, TradeResult, candlestick, TimeFrame, CandleAggregator, trade, ticker, all_tickers, book, Side, balance, BalanceResult, PositionBalance, Balance, IndexResult, Index, index, MarkPriceResult, MarkPrice, mark, SettlementResult, Settlement, settlement, FundingRateResult, EstimatedFundingRateResult, FundingRate, funding, estimated_funding, OtcBookResult, OtcBook, OtcQuote, otc_book, PositionResult, Position, PositionBalanceResult, PositionBalanceUpdate, BalanceUpdate, positions, position_balance, InstrumentsResult, InstrumentInfo, AccountSummaryResult, OrderResult, Order, OrderListResult, OrderPage, user_order, UserTradeResult, UserTrade, user_trade, Channel};
+pub use model::string_number;
+pub use client::{CryptoClient, CryptoError, HeartbeatMode, ParseFailurePolicy};
+pub use builder::{CryptoClientBuilder, BackoffPolicy, RateLimit};
+pub use health::{Health, ConnectionState};
+pub use tokio_tungstenite::tungstenite::protocol::WebSocketConfig;
+pub use message::{SubscribeResult, Envelope};
 
 #[cfg(test)]
 mod tests {
@@ -1,5 +1,12 @@
-use serde::Deserialize;
-use crate::model::{TradeResult, CandlestickResult, TickerResult, BookResult, BalanceResult};
+use serde::de::{self, DeserializeSeed, IgnoredAny, IntoDeserializer, MapAccess, Visitor};
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_json::value::RawValue;
+use std::borrow::Cow;
+use std::fmt;
+use std::sync::Arc;
+use std::time::{Instant, SystemTime};
+use crate::model::{TradeResult, CandlestickResult, TickerResult, BookResult, BalanceResult, IndexResult, MarkPriceResult, SettlementResult, FundingRateResult, EstimatedFundingRateResult, OtcBookResult, PositionResult, PositionBalanceResult, InstrumentsResult, BookSnapshotResult, AccountSummaryResult, OrderResult, OrderListResult, UserTradeResult};
+use crate::CryptoError;
 
 ///All kind of incoming market messages that the client receive and understand
 #[derive(Deserialize, Debug)]
@@ -36,11 +43,206 @@ pub enum Message {
     UnsubscriptionResponse{
         id: i32,
         code: u64
+    },
+
+    /// A response from an instruments request
+    #[serde(rename = "public/get-instruments")]
+    InstrumentsResponse{
+        id: u64,
+        code: u64,
+        message: Option<String>,
+        #[serde(default)]
+        result: InstrumentsResult
+    },
+
+    /// A response from a book request
+    #[serde(rename = "public/get-book")]
+    BookResponse{
+        id: u64,
+        code: u64,
+        message: Option<String>,
+        result: Option<BookSnapshotResult>
+    },
+
+    /// A response from an account summary request
+    #[serde(rename = "private/get-account-summary")]
+    AccountSummaryResponse{
+        id: u64,
+        code: u64,
+        message: Option<String>,
+        #[serde(default)]
+        result: AccountSummaryResult
+    },
+
+    /// A response from an open orders request
+    #[serde(rename = "private/get-open-orders")]
+    OpenOrdersResponse{
+        id: u64,
+        code: u64,
+        message: Option<String>,
+        #[serde(default)]
+        result: OrderListResult
+    },
+
+    /// A response from an order history request
+    #[serde(rename = "private/get-order-history")]
+    OrderHistoryResponse{
+        id: u64,
+        code: u64,
+        message: Option<String>,
+        #[serde(default)]
+        result: OrderListResult
+    }
+}
+
+/// The fields of a frame needed to tell how to parse it, borrowed from the text
+struct Frame<'a> {
+    method: Cow<'a, str>,
+    id: Option<i64>,
+    code: u64,
+    channel: Option<Cow<'a, str>>,
+    message: Option<Cow<'a, str>>,
+    result: Option<FrameResult<'a>>,
+}
+
+/// The `result` of a frame, parsed on the spot when the `method` before it says it is a
+/// subscription, kept as text otherwise
+enum FrameResult<'a> {
+    Parsed(SubscribeResult),
+    Raw(&'a RawValue),
+}
+
+/// A key or a string of a frame, borrowed from the text unless it has escapes
+#[derive(Deserialize)]
+struct Text<'a>(#[serde(borrow)] Cow<'a, str>);
+
+impl<'de> Deserialize<'de> for Frame<'de> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_map(FrameVisitor)
+    }
+}
+
+struct FrameVisitor;
+
+impl<'de> Visitor<'de> for FrameVisitor {
+    type Value = Frame<'de>;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a frame")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Frame<'de>, A::Error> {
+        let mut method = None;
+        let mut id = None;
+        let mut code = 0;
+        let mut channel = None;
+        let mut message = None;
+        let mut result = None;
+        while let Some(Text(key)) = map.next_key()? {
+            match key.as_ref() {
+                "method" => method = Some(map.next_value::<Text>()?.0),
+                "id" => id = map.next_value()?,
+                "code" => code = map.next_value()?,
+                "channel" => channel = map.next_value::<Option<Text>>()?.map(|text| text.0),
+                "message" => message = map.next_value::<Option<Text>>()?.map(|text| text.0),
+                "result" if method.as_deref() == Some("subscribe") => {
+                    result = map.next_value::<Option<Streamed>>()?.map(|result| FrameResult::Parsed(result.0))
+                },
+                "result" => result = map.next_value::<Option<&RawValue>>()?.map(FrameResult::Raw),
+                _ => {
+                    map.next_value::<IgnoredAny>()?;
+                }
+            }
+        }
+        let method = method.ok_or_else(|| de::Error::missing_field("method"))?;
+        Ok(Frame { method, id, code, channel, message, result })
+    }
+}
+
+/// Parse a frame of the exchange.
+///
+/// The tagged enums buffer the whole frame into an intermediate tree before parsing the
+/// variant, twice for a subscription result. Subscription frames, by far the most frequent,
+/// are read here in one pass without that tree: the exchange sends the `method` before the
+/// `result` and the `channel` before the `data`, so the result is parsed as soon as it is
+/// reached. The few fields of the result before its `channel` are kept as text and parsed
+/// when the channel is known; a `result` before the `method` is parsed once more at the end.
+///
+/// The models still own their strings: the results are moved to the dispatcher task and
+/// to the callbacks, which outlive the text of the frame.
+pub(crate) fn parse(text: &str) -> Result<Message, serde_json::Error> {
+    let frame: Frame = serde_json::from_str(text)?;
+    match (frame.method.as_ref(), frame.id) {
+        ("subscribe", Some(id)) => Ok(Message::SubscriptionResponse {
+            result: match frame.result {
+                Some(FrameResult::Parsed(result)) => Some(result),
+                Some(FrameResult::Raw(result)) => Some(SubscribeResult::from_json(result.get())?),
+                None => None,
+            },
+            id,
+            code: frame.code,
+            channel: frame.channel.map(Cow::into_owned),
+            message: frame.message.map(Cow::into_owned),
+        }),
+        _ => serde_json::from_str(text),
+    }
+}
+
+/// The `message` of a frame
+#[derive(Deserialize)]
+struct Notice {
+    message: Option<String>,
+}
+
+/// True if `text`, a message or a close reason of the exchange, is about a maintenance
+pub(crate) fn mentions_maintenance(text: &str) -> bool {
+    text.to_ascii_lowercase().contains("maintenance")
+}
+
+/// The `message` of a frame that could not be parsed, if it announces a maintenance
+pub(crate) fn maintenance_notice(text: &str) -> Option<String> {
+    let notice: Notice = serde_json::from_str(text).ok()?;
+    notice.message.filter(|message| mentions_maintenance(message))
+}
+
+/// The typed result of a request, handed to the task waiting for it
+#[derive(Debug)]
+pub enum Response {
+    /// Result of `public/get-instruments`
+    Instruments(InstrumentsResult),
+
+    /// Result of `public/get-book`
+    Book(Option<BookSnapshotResult>),
+
+    /// Result of `private/get-account-summary`
+    AccountSummary(AccountSummaryResult),
+
+    /// Result of `private/get-open-orders`
+    OpenOrders(OrderListResult),
+
+    /// Result of `private/get-order-history`
+    OrderHistory(OrderListResult),
+
+    /// Ack of an `unsubscribe` sent by `disconnect`
+    Unsubscription,
+}
+
+impl Response {
+    /// The method of the frame this response was read from
+    pub fn method(&self) -> &'static str {
+        match self {
+            Response::Instruments(_) => "public/get-instruments",
+            Response::Book(_) => "public/get-book",
+            Response::AccountSummary(_) => "private/get-account-summary",
+            Response::OpenOrders(_) => "private/get-open-orders",
+            Response::OrderHistory(_) => "private/get-order-history",
+            Response::Unsubscription => "unsubscribe",
+        }
     }
 }
 
 /// The result of a subscribed event. Identified by the field 'channel'
-#[derive(Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 #[serde(tag = "channel")]
 pub enum SubscribeResult {
 
@@ -62,22 +264,253 @@ pub enum SubscribeResult {
     #[serde(rename = "book")]
     BookResult(BookResult),
 
+    /// OTC book subscription result
+    #[serde(rename = "otc_book")]
+    OtcBookResult(OtcBookResult),
+
+    /// Index price subscription result
+    #[serde(rename = "index")]
+    IndexResult(IndexResult),
+
+    /// Mark price subscription result
+    #[serde(rename = "mark")]
+    MarkPriceResult(MarkPriceResult),
+
+    /// Settlement price subscription result
+    #[serde(rename = "settlement")]
+    SettlementResult(SettlementResult),
+
+    /// Funding rate subscription result
+    #[serde(rename = "funding")]
+    FundingRateResult(FundingRateResult),
+
+    /// Estimated funding rate subscription result
+    #[serde(rename = "estimatedfunding")]
+    EstimatedFundingRateResult(EstimatedFundingRateResult),
+
     //// USER ////
 
     /// Trade subscription result
     #[serde(rename = "user.balance")]
     BalanceResult(BalanceResult),
 
+    /// Order subscription result
+    #[serde(rename = "user.order")]
+    OrderResult(OrderResult),
+
+    /// Executions of the orders of the user
+    #[serde(rename = "user.trade")]
+    UserTradeResult(UserTradeResult),
+
+    /// Positions subscription result
+    #[serde(rename = "user.positions")]
+    PositionResult(PositionResult),
+
+    /// Position and balance subscription result
+    #[serde(rename = "user.position_balance")]
+    PositionBalanceResult(PositionBalanceResult),
+
 
     AuthResult{
         success: bool
     },
 
+    /// A heartbeat of the exchange, only delivered with `HeartbeatMode::Manual`
+    HeartbeatRequest{
+        id: u64
+    },
+
     UnsubscriptionResult{
         success: bool
     },
+
+    /// The connection was lost and opened again after `attempt` attempts,
+    /// the subscriptions are restored
+    Reconnected{
+        attempt: u32
+    },
+
+    /// The exchange stopped considering the session authenticated, it was authenticated
+    /// again after `attempt` attempts and the user channels subscribed again.
+    /// Updates may have been missed meanwhile
+    Reauthenticated{
+        attempt: u32
+    },
+
+    /// A subscribe request to several channels where the exchange rejected some of them,
+    /// with the error code of each rejected channel. Only the accepted ones are
+    /// subscribed again after a reconnection
+    PartialSubscription{
+        accepted: Vec<String>,
+        rejected: Vec<(String, u64)>
+    },
+
+    /// The connection is back after a maintenance of the exchange, delivered after
+    /// `Reconnected`
+    MaintenanceEnded{
+        attempt: u32
+    },
+}
+
+impl SubscribeResult {
+    /// The `subscription` field of the channel results, like `book.ETH_CRO.10`
+    pub fn subscription(&self) -> Option<&str> {
+        let subscription = match self {
+            SubscribeResult::TradeResult(result) => &result.subscription,
+            SubscribeResult::CandlestickResult(result) => &result.subscription,
+            SubscribeResult::TickerResult(result) => &result.subscription,
+            SubscribeResult::BookResult(result) => &result.subscription,
+            SubscribeResult::OtcBookResult(result) => &result.subscription,
+            SubscribeResult::IndexResult(result) => &result.subscription,
+            SubscribeResult::MarkPriceResult(result) => &result.subscription,
+            SubscribeResult::SettlementResult(result) => &result.subscription,
+            SubscribeResult::FundingRateResult(result) => &result.subscription,
+            SubscribeResult::EstimatedFundingRateResult(result) => &result.subscription,
+            SubscribeResult::BalanceResult(result) => &result.subscription,
+            SubscribeResult::OrderResult(result) => &result.subscription,
+            SubscribeResult::UserTradeResult(result) => &result.subscription,
+            SubscribeResult::PositionResult(result) => &result.subscription,
+            SubscribeResult::PositionBalanceResult(result) => &result.subscription,
+            SubscribeResult::AuthResult { .. }
+            | SubscribeResult::HeartbeatRequest { .. }
+            | SubscribeResult::UnsubscriptionResult { .. }
+            | SubscribeResult::Reconnected { .. }
+            | SubscribeResult::Reauthenticated { .. }
+            | SubscribeResult::PartialSubscription { .. }
+            | SubscribeResult::MaintenanceEnded { .. } => return None,
+        };
+        Some(subscription)
+    }
+
+    /// Parse a result, same as `serde_json::from_str` but the text is not buffered
+    /// to find the `channel` first. Only the fields before the `channel` are kept as
+    /// text and parsed again, the `data` is read once
+    pub fn from_json(text: &str) -> Result<SubscribeResult, serde_json::Error> {
+        let mut deserializer = serde_json::Deserializer::from_str(text);
+        let result = Streamed::deserialize(&mut deserializer)?;
+        deserializer.end()?;
+        Ok(result.0)
+    }
+}
+
+/// A result parsed as it is read, see [`SubscribeResult::from_json`]
+struct Streamed(SubscribeResult);
+
+impl<'de> Deserialize<'de> for Streamed {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_map(StreamedVisitor).map(Streamed)
+    }
+}
+
+struct StreamedVisitor;
+
+impl<'de> Visitor<'de> for StreamedVisitor {
+    type Value = SubscribeResult;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a subscription result")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<SubscribeResult, A::Error> {
+        let mut fields = Vec::new();
+        let mut channel = None;
+        while let Some(Text(key)) = map.next_key()? {
+            let value: &'de RawValue = map.next_value()?;
+            let is_channel = key == "channel";
+            fields.push((key, value));
+            if is_channel {
+                let name: Text = serde_json::from_str(value.get()).map_err(de::Error::custom)?;
+                channel = Some(name.0);
+                break;
+            }
+        }
+
+        let rest = Replay { fields: fields.into_iter(), value: None, map };
+        Ok(match channel.as_deref() {
+            Some("trade") => SubscribeResult::TradeResult(Deserialize::deserialize(rest)?),
+            Some("candlestick") => SubscribeResult::CandlestickResult(Deserialize::deserialize(rest)?),
+            Some("ticker") => SubscribeResult::TickerResult(Deserialize::deserialize(rest)?),
+            Some("book") => SubscribeResult::BookResult(Deserialize::deserialize(rest)?),
+            Some("otc_book") => SubscribeResult::OtcBookResult(Deserialize::deserialize(rest)?),
+            Some("index") => SubscribeResult::IndexResult(Deserialize::deserialize(rest)?),
+            Some("mark") => SubscribeResult::MarkPriceResult(Deserialize::deserialize(rest)?),
+            Some("settlement") => SubscribeResult::SettlementResult(Deserialize::deserialize(rest)?),
+            Some("funding") => SubscribeResult::FundingRateResult(Deserialize::deserialize(rest)?),
+            Some("estimatedfunding") => SubscribeResult::EstimatedFundingRateResult(Deserialize::deserialize(rest)?),
+            Some("user.balance") => SubscribeResult::BalanceResult(Deserialize::deserialize(rest)?),
+            Some("user.order") => SubscribeResult::OrderResult(Deserialize::deserialize(rest)?),
+            Some("user.trade") => SubscribeResult::UserTradeResult(Deserialize::deserialize(rest)?),
+            Some("user.positions") => SubscribeResult::PositionResult(Deserialize::deserialize(rest)?),
+            Some("user.position_balance") => SubscribeResult::PositionBalanceResult(Deserialize::deserialize(rest)?),
+            // The rare ones, and the errors of an unknown or missing channel
+            _ => Deserialize::deserialize(rest)?,
+        })
+    }
+}
+
+/// The fields of a result: the ones kept as text, then the rest of the map
+struct Replay<'de, A> {
+    fields: std::vec::IntoIter<(Cow<'de, str>, &'de RawValue)>,
+    value: Option<&'de RawValue>,
+    map: A,
+}
+
+impl<'de, A: MapAccess<'de>> MapAccess<'de> for Replay<'de, A> {
+    type Error = A::Error;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(&mut self, seed: K) -> Result<Option<K::Value>, A::Error> {
+        match self.fields.next() {
+            Some((key, value)) => {
+                self.value = Some(value);
+                seed.deserialize(IntoDeserializer::<A::Error>::into_deserializer(key)).map(Some)
+            },
+            None => self.map.next_key_seed(seed),
+        }
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value, A::Error> {
+        match self.value.take() {
+            Some(value) => seed.deserialize(value).map_err(de::Error::custom),
+            None => self.map.next_value_seed(seed),
+        }
+    }
+}
+
+impl<'de, A: MapAccess<'de>> Deserializer<'de> for Replay<'de, A> {
+    type Error = A::Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, A::Error> {
+        visitor.visit_map(self)
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf option unit unit_struct newtype_struct seq tuple
+        tuple_struct map struct enum identifier ignored_any
+    }
 }
 
+/// A delivered result together with the moment its frame was read from the socket.
+///
+/// Both timestamps are taken before the frame is parsed, so the parsing time is
+/// included when they are compared with the exchange `t` fields.
+#[derive(Debug)]
+pub struct Envelope {
+    /// Wall clock time when the frame was read, comparable with the exchange timestamps
+    pub received_at: SystemTime,
+
+    /// Monotonic time when the frame was read, to measure the delay inside the process
+    pub received_instant: Instant,
+
+    /// The parsed result or the error produced while handling the frame
+    pub result: Result<SubscribeResult, CryptoError>,
+
+    /// Text of the frame, only with `CryptoClientBuilder::with_raw_passthrough`. The results
+    /// not coming from a frame, like `Reconnected` or `AckTimeout`, have none. The results
+    /// split by instrument out of an all-instrument ticker frame share the text of the whole
+    /// frame, with the tickers of every instrument
+    pub raw: Option<Arc<str>>,
+}
 
 #[cfg(test)]
 mod tests {
@@ -85,6 +518,30 @@ mod tests {
     use serde_json::from_str;
 
 
+    #[test]
+    fn check_clone_and_serialize() {
+        let json_sub = "{
+            \"channel\": \"book\", \"instrument_name\": \"ETH_CRO\", \"subscription\": \"book.ETH_CRO.10\", \"depth\": 10,
+            \"data\": [{\"bids\": [[\"11746.488\", \"128\", \"8\"]], \"asks\": [], \"t\": 1587523078844}]
+          }";
+        let res = from_str::<SubscribeResult>(json_sub).unwrap();
+
+        let SubscribeResult::BookResult(book_result) = &res else {
+            panic!("unexpected result");
+        };
+        let cloned = book_result.clone();
+        assert_eq!(&cloned, book_result);
+        assert_eq!(res.clone(), res);
+
+        // The channel tag is written back
+        let text = serde_json::to_string(&res).unwrap();
+        assert_eq!(from_str::<serde_json::Value>(&text).unwrap(), from_str::<serde_json::Value>(json_sub).unwrap());
+        assert_eq!(from_str::<SubscribeResult>(&text).unwrap(), res);
+
+        let auth = serde_json::to_string(&SubscribeResult::AuthResult { success: true }).unwrap();
+        assert_eq!(auth, "{\"channel\":\"AuthResult\",\"success\":true}");
+    }
+
     #[test]
     fn check_result_trade_structure() {
         let json_sub = "{
@@ -98,7 +555,7 @@ mod tests {
                 
             },
             _ => {
-                assert!(false);
+                panic!("unexpected result");
             }
         }
 
@@ -118,7 +575,7 @@ mod tests {
                 assert_eq!(result.interval, "5m");
             },
             _ => {
-                assert!(false);
+                panic!("unexpected result");
             }
         }
     }
@@ -139,7 +596,7 @@ mod tests {
                 assert_eq!(result.interval, "5m");
             },
             _ => {
-                assert!(false);
+                panic!("unexpected result");
             }
         }
     }
@@ -156,7 +613,7 @@ mod tests {
                 assert_eq!(result.subscription, "sub");
             },
             _ => {
-                assert!(false);
+                panic!("unexpected result");
             }
         }
     }
@@ -175,8 +632,198 @@ mod tests {
                 assert_eq!(result.depth, 123);
             },
             _ => {
-                assert!(false);
+                panic!("unexpected result");
+            }
+        }
+    }
+
+    #[test]
+    fn check_result_index_structure() {
+        let json_sub = "{
+            \"channel\": \"index\", \"instrument_name\": \"BTCUSD-INDEX\", \"subscription\": \"index.BTCUSD-INDEX\", \"data\": [{\"v\": \"51204.48000\", \"t\": 1613580710000}]
+          }";
+        let res = from_str::<SubscribeResult>(json_sub).unwrap();
+
+        match res {
+            SubscribeResult::IndexResult(result) => {
+                assert_eq!(result.instrument_name, "BTCUSD-INDEX");
+                assert_eq!(result.subscription, "index.BTCUSD-INDEX");
+                assert_eq!(result.data[0].value, 51204.48);
+            },
+            _ => {
+                panic!("unexpected result");
+            }
+        }
+    }
+
+    #[test]
+    fn check_result_mark_price_structure() {
+        let json_sub = "{
+            \"channel\": \"mark\", \"instrument_name\": \"BTCUSD-PERP\", \"subscription\": \"mark.BTCUSD-PERP\", \"data\": [{\"v\": \"50490.1\", \"t\": 1613580710000}]
+          }";
+        let res = from_str::<SubscribeResult>(json_sub).unwrap();
+
+        match res {
+            SubscribeResult::MarkPriceResult(result) => {
+                assert_eq!(result.instrument_name, "BTCUSD-PERP");
+                assert_eq!(result.subscription, "mark.BTCUSD-PERP");
+                assert_eq!(result.data[0].value, 50490.1);
+            },
+            _ => {
+                panic!("unexpected result");
             }
         }
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn check_result_settlement_empty_data() {
+        let json_sub = "{
+            \"channel\": \"settlement\", \"instrument_name\": \"BTCUSD-210528m2\", \"subscription\": \"settlement.BTCUSD-210528m2\", \"data\": []
+          }";
+        let res = from_str::<SubscribeResult>(json_sub).unwrap();
+
+        match res {
+            SubscribeResult::SettlementResult(result) => {
+                assert_eq!(result.instrument_name, "BTCUSD-210528m2");
+                assert!(result.data.is_empty());
+            },
+            _ => {
+                panic!("unexpected result");
+            }
+        }
+    }
+
+    #[test]
+    fn check_result_funding_structure() {
+        let json_sub = "{
+            \"channel\": \"funding\", \"instrument_name\": \"BTCUSD-PERP\", \"subscription\": \"funding.BTCUSD-PERP\", \"data\": [{\"v\": \"-0.00005\", \"t\": 1624003200000}]
+          }";
+        let res = from_str::<SubscribeResult>(json_sub).unwrap();
+
+        match res {
+            SubscribeResult::FundingRateResult(result) => {
+                assert_eq!(result.instrument_name, "BTCUSD-PERP");
+                assert_eq!(result.data[0].value, -0.00005);
+            },
+            _ => {
+                panic!("unexpected result");
+            }
+        }
+
+        let json_sub = "{
+            \"channel\": \"estimatedfunding\", \"instrument_name\": \"BTCUSD-PERP\", \"subscription\": \"estimatedfunding.BTCUSD-PERP\", \"data\": []
+          }";
+        let res = from_str::<SubscribeResult>(json_sub).unwrap();
+        assert!(matches!(res, SubscribeResult::EstimatedFundingRateResult(_)));
+    }
+
+    #[test]
+    fn check_result_otc_book_structure() {
+        let json_sub = "{
+            \"channel\": \"otc_book\", \"instrument_name\": \"BTC_USDT\", \"subscription\": \"otc_book.BTC_USDT\", \"data\": [{\"asks\": [], \"bids\": [[\"50120.5\", \"0.1\", \"1\"]], \"t\": 1613580710768, \"tt\": 1613580715768}]
+          }";
+        let res = from_str::<SubscribeResult>(json_sub).unwrap();
+
+        match res {
+            SubscribeResult::OtcBookResult(result) => {
+                assert_eq!(result.instrument_name, "BTC_USDT");
+                assert!(result.data[0].asks.is_empty());
+                assert_eq!(result.data[0].bids[0].price, 50120.5);
+            },
+            _ => {
+                panic!("unexpected result");
+            }
+        }
+    }
+
+    #[test]
+    fn check_result_positions_structure() {
+        let json_sub = "{
+            \"channel\": \"user.positions\", \"subscription\": \"user.positions\", \"data\": []
+          }";
+        let res = from_str::<SubscribeResult>(json_sub).unwrap();
+        assert!(matches!(res, SubscribeResult::PositionResult(_)));
+
+        let json_sub = "{
+            \"channel\": \"user.position_balance\", \"subscription\": \"user.position_balance\", \"data\": [{\"balances\": [], \"positions\": []}]
+          }";
+        let res = from_str::<SubscribeResult>(json_sub).unwrap();
+        assert!(matches!(res, SubscribeResult::PositionBalanceResult(_)));
+    }
+
+    #[test]
+    fn check_result_order_structure() {
+        let json_sub = "{
+            \"channel\": \"user.order\", \"instrument_name\": \"ETH_CRO\", \"subscription\": \"user.order.ETH_CRO\", \"data\": []
+          }";
+        let res = from_str::<SubscribeResult>(json_sub).unwrap();
+
+        match res {
+            SubscribeResult::OrderResult(result) => {
+                assert_eq!(result.instrument_name, "ETH_CRO");
+                assert_eq!(result.subscription, "user.order.ETH_CRO");
+            },
+            _ => {
+                panic!("unexpected result");
+            }
+        }
+    }
+
+    #[test]
+    fn check_from_json_matches_the_derive() {
+        let results = [
+            "{\"channel\": \"book\", \"instrument_name\": \"ETH_CRO\", \"subscription\": \"book.ETH_CRO.10\", \"depth\": 10, \"data\": [{\"bids\": [[\"11746.488\", \"128\", \"8\"]], \"asks\": [[11747.488, 201, 12]], \"t\": 1587523078844}]}",
+            "{\"channel\": \"trade\", \"instrument_name\": \"instrument\", \"subscription\": \"sub\", \"data\": []}",
+            "{\"channel\": \"candlestick\", \"instrument_name\": \"instrument\", \"subscription\": \"sub\", \"interval\": \"5m\", \"data\": []}",
+            "{\"channel\": \"ticker\", \"instrument_name\": \"instrument\", \"subscription\": \"sub\", \"data\": []}",
+            "{\"channel\": \"index\", \"instrument_name\": \"BTCUSD-INDEX\", \"subscription\": \"index.BTCUSD-INDEX\", \"data\": [{\"v\": \"51204.48000\", \"t\": 1613580710000}]}",
+            "{\"channel\": \"funding\", \"instrument_name\": \"BTCUSD-PERP\", \"subscription\": \"funding.BTCUSD-PERP\", \"data\": [{\"v\": \"-0.00005\", \"t\": 1624003200000}]}",
+            "{\"channel\": \"user.position_balance\", \"subscription\": \"user.position_balance\", \"data\": [{\"balances\": [], \"positions\": []}]}",
+            "{\"channel\": \"user.trade\", \"instrument_name\": \"ETH_CRO\", \"subscription\": \"user.trade.ETH_CRO\", \"data\": [{\"side\": \"BUY\", \"instrument_name\": \"ETH_CRO\", \"fee\": 0.014, \"trade_id\": \"1\", \"create_time\": 1588777459755, \"traded_price\": 7, \"traded_quantity\": 1, \"fee_currency\": \"CRO\", \"order_id\": \"2\"}]}",
+            "{\"channel\": \"AuthResult\", \"success\": true}",
+            "{\"data\": [{\"v\": \"51204.48000\", \"t\": 1613580710000}], \"instrument_name\": \"BTCUSD-INDEX\", \"channel\": \"index\", \"subscription\": \"index.BTCUSD-INDEX\"}",
+            "{\"subscription\": \"sub\", \"data\": [], \"channel\": \"AuthResult\", \"success\": true}",
+            "{\"chan\\u006eel\": \"trade\", \"instrument_name\": \"ETH_\\u0043RO\", \"subscription\": \"sub\", \"data\": []}",
+        ];
+        for text in results {
+            assert_eq!(SubscribeResult::from_json(text).unwrap(), from_str::<SubscribeResult>(text).unwrap(), "{text}");
+        }
+
+        assert!(SubscribeResult::from_json("{\"channel\": \"unknown\", \"data\": []}").is_err());
+        assert!(SubscribeResult::from_json("{\"channel\": \"book\", \"data\": []}").is_err());
+        assert!(SubscribeResult::from_json("{\"data\": []}").is_err());
+        assert!(SubscribeResult::from_json("{\"channel\": \"trade\", \"data\": []} trailing").is_err());
+    }
+
+    #[test]
+    fn check_parse_matches_the_derive() {
+        let frames = [
+            "{\"id\": -1, \"method\": \"subscribe\", \"code\": 0, \"result\": {\"channel\": \"trade\", \"instrument_name\": \"ETH_CRO\", \"subscription\": \"trade.ETH_CRO\", \"data\": []}}",
+            "{\"id\": 1, \"method\": \"subscribe\", \"code\": 0}",
+            "{\"id\": 2, \"method\": \"subscribe\", \"code\": 10004, \"message\": \"BAD_REQUEST\", \"channel\": \"book.X.10\"}",
+            "{\"id\": 42, \"method\": \"public/heartbeat\"}",
+            "{\"id\": 3, \"method\": \"public/auth\", \"code\": 0}",
+            "{\"id\": 4, \"method\": \"unsubscribe\", \"code\": 0}",
+            "{\"result\": {\"channel\": \"trade\", \"instrument_name\": \"ETH_CRO\", \"subscription\": \"trade.ETH_CRO\", \"data\": []}, \"id\": -1, \"method\": \"subscribe\", \"code\": 0}",
+            "{\"id\": 5, \"method\": \"subscribe\", \"code\": 0, \"result\": null, \"extra\": [1, {\"a\": 2}]}",
+        ];
+        for text in frames {
+            let parsed = parse(text).unwrap();
+            let derived = from_str::<Message>(text).unwrap();
+            assert_eq!(format!("{parsed:?}"), format!("{derived:?}"), "{text}");
+        }
+
+        assert!(parse("{\"id\": 1, \"method\": \"unknown\"}").is_err());
+        assert!(parse("{\"id\": 1, \"method\": \"subscribe\", \"code\": 0, \"result\": {\"channel\": \"book\"}}").is_err());
+        assert!(parse("not json").is_err());
+    }
+
+    #[test]
+    fn check_maintenance_notice() {
+        let notice = "{\"id\": -1, \"method\": \"public/notice\", \"code\": 0, \"message\": \"Scheduled MAINTENANCE until 03:00 UTC\"}";
+        assert_eq!(maintenance_notice(notice).as_deref(), Some("Scheduled MAINTENANCE until 03:00 UTC"));
+        assert_eq!(maintenance_notice("{\"id\": 1, \"method\": \"unknown\", \"message\": \"BAD_REQUEST\"}"), None);
+        assert_eq!(maintenance_notice("{\"id\": 1, \"method\": \"unknown\"}"), None);
+        assert_eq!(maintenance_notice("not json"), None);
+    }
+}
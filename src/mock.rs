@@ -0,0 +1,118 @@
+use futures::{SinkExt, StreamExt};
+use serde_json::Value;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::net::TcpListener;
+use tokio::sync::{mpsc, Mutex};
+use tokio_tungstenite::accept_async;
+use tokio_tungstenite::tungstenite::protocol::Message;
+
+/// Builds the replies of the server for every text frame received
+type Responder = Arc<dyn Fn(&Value) -> Vec<Message> + Send + Sync>;
+
+/// Scriptable websocket server used by the client tests.
+///
+/// Frames pushed with `push` are sent to the connected client (they are queued
+/// if nobody is connected yet) and every frame received from the client is
+/// recorded so the test can assert the exact sequence.
+pub(crate) struct MockServer {
+    /// Url the client has to connect to
+    pub(crate) url: String,
+    push: mpsc::UnboundedSender<Message>,
+    received: mpsc::UnboundedReceiver<Message>,
+}
+
+impl MockServer {
+    /// A server that never answers, it only sends what the test pushes
+    pub(crate) async fn start() -> MockServer {
+        MockServer::with_responder(|_| Vec::new()).await
+    }
+
+    /// A server that answers every text frame with the frames built by `responder`
+    pub(crate) async fn with_responder(
+        responder: impl Fn(&Value) -> Vec<Message> + Send + Sync + 'static,
+    ) -> MockServer {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("ws://{}", listener.local_addr().unwrap());
+        let (push, push_rx) = mpsc::unbounded_channel::<Message>();
+        let (received_tx, received) = mpsc::unbounded_channel::<Message>();
+        let push_rx = Arc::new(Mutex::new(push_rx));
+        let responder: Responder = Arc::new(responder);
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let Ok(mut ws) = accept_async(stream).await else {
+                    continue;
+                };
+                let push_rx = push_rx.clone();
+                let responder = responder.clone();
+                let received_tx = received_tx.clone();
+                tokio::spawn(async move {
+                    // Only one connection at a time consumes the pushed frames
+                    let mut push_rx = push_rx.lock().await;
+                    loop {
+                        tokio::select! {
+                            incoming = ws.next() => {
+                                let Some(Ok(message)) = incoming else { break };
+                                let replies = match &message {
+                                    Message::Text(text) => serde_json::from_str::<Value>(text)
+                                        .map(|value| responder(&value))
+                                        .unwrap_or_default(),
+                                    _ => Vec::new(),
+                                };
+                                received_tx.send(message).ok();
+                                for reply in replies {
+                                    if ws.send(reply).await.is_err() {
+                                        break;
+                                    }
+                                }
+                            }
+                            Some(message) = push_rx.recv() => {
+                                if ws.send(message).await.is_err() {
+                                    break;
+                                }
+                            }
+                        }
+                    }
+                });
+            }
+        });
+
+        MockServer {
+            url,
+            push,
+            received,
+        }
+    }
+
+    /// Send a frame to the client
+    pub(crate) fn push(&self, message: Message) {
+        self.push.send(message).unwrap();
+    }
+
+    /// Send a text frame to the client
+    pub(crate) fn push_text(&self, text: &str) {
+        self.push(Message::text(text));
+    }
+
+    /// Next frame received from the client, `None` if nothing arrives in a few seconds
+    pub(crate) async fn recv(&mut self) -> Option<Message> {
+        self.recv_timeout(Duration::from_secs(5)).await
+    }
+
+    /// Next frame received from the client, `None` if nothing arrives in `timeout`
+    pub(crate) async fn recv_timeout(&mut self, timeout: Duration) -> Option<Message> {
+        tokio::time::timeout(timeout, self.received.recv())
+            .await
+            .ok()
+            .flatten()
+    }
+
+    /// Next text frame received from the client, parsed as json
+    pub(crate) async fn recv_request(&mut self) -> Option<Value> {
+        loop {
+            if let Message::Text(text) = self.recv().await? {
+                return serde_json::from_str(&text).ok();
+            }
+        }
+    }
+}
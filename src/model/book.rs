@@ -3,10 +3,14 @@ use serde::{
     ser::SerializeTuple,
     Deserialize, Deserializer, Serialize, Serializer,
 };
-use serde_aux::prelude::deserialize_number_from_string;
+use serde_aux::prelude::deserialize_option_number_from_string;
+use std::fmt::Display;
+
+use super::common::BookSide;
+use super::string_number::{self, WithText};
 
 // Main container of a book
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct BookResult {
     /// Just the instrument name
     pub instrument_name: String,
@@ -21,7 +25,7 @@ pub struct BookResult {
     pub data: Vec<Book>,
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Default)]
 pub struct Offer {
     /// price
     pub price: f64,
@@ -31,22 +35,47 @@ pub struct Offer {
 
     /// number of orders
     pub amount: f64,
+
+    /// Text of the price as sent by the exchange, only when it is not the usual
+    /// one like `"50126.000000"`. Written back when serializing
+    pub price_raw: Option<String>,
+
+    /// Text of the quantity, like `price_raw`
+    pub quantity_raw: Option<String>,
+
+    /// Text of the number of orders, like `price_raw`
+    pub amount_raw: Option<String>,
+}
+
+/// Serializes a tuple element as a string, its original text if there is one
+pub(super) struct AsString<'a, T>(pub(super) &'a T, pub(super) Option<&'a str>);
+
+impl<T: Display> Serialize for AsString<'_, T> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        string_number::serialize_with_text(self.0, self.1, serializer)
+    }
 }
 
-/// Convert the struct into the tuple format
+/// Convert the struct into the tuple format, with the numbers as strings like the exchange
 impl Serialize for Offer {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
         S: Serializer,
     {
         let mut tup = serializer.serialize_tuple(3)?;
-        tup.serialize_element(&self.price)?;
-        tup.serialize_element(&self.quantity)?;
-        tup.serialize_element(&self.amount)?;
+        tup.serialize_element(&AsString(&self.price, self.price_raw.as_deref()))?;
+        tup.serialize_element(&AsString(&self.quantity, self.quantity_raw.as_deref()))?;
+        tup.serialize_element(&AsString(&self.amount, self.amount_raw.as_deref()))?;
         tup.end()
     }
 }
 
+/// The exchange sends the offer elements as strings but plain numbers are accepted too,
+/// the levels of older book payloads are numbers. The strings are parsed in place and
+/// only copied when they are not written the usual way, see `Offer::price_raw`
 struct OfferVisitor;
 /// Convert the tuple into a struct
 impl<'de> Visitor<'de> for OfferVisitor {
@@ -63,24 +92,27 @@ impl<'de> Visitor<'de> for OfferVisitor {
     where
         M: SeqAccess<'de>,
     {
-        let price_str: String = seq
+        let price: WithText = seq
             .next_element()?
             .ok_or_else(|| serde::de::Error::custom("Missing price"))?;
-        let quantity_str: String = seq
+        let quantity: WithText = seq
             .next_element()?
             .ok_or_else(|| serde::de::Error::custom("Missing quantity"))?;
-        let amount_str: String = seq
+        let amount: WithText = seq
             .next_element()?
             .ok_or_else(|| serde::de::Error::custom("Missing amount"))?;
 
-        let price = price_str.parse::<f64>().map_err(de::Error::custom)?;
-        let quantity = quantity_str.parse::<f64>().map_err(de::Error::custom)?;
-        let amount = amount_str.parse::<f64>().map_err(de::Error::custom)?;
+        if seq.next_element::<de::IgnoredAny>()?.is_some() {
+            return Err(de::Error::invalid_length(4, &self));
+        }
 
         Ok(Offer {
-            price,
-            quantity,
-            amount,
+            price: price.value,
+            quantity: quantity.value,
+            amount: amount.value,
+            price_raw: price.text,
+            quantity_raw: quantity.text,
+            amount_raw: amount.text,
         })
     }
 }
@@ -94,8 +126,22 @@ impl<'de> Deserialize<'de> for Offer {
     }
 }
 
+/// Result of a `public/get-book` request
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct BookSnapshotResult {
+    /// Just the instrument name
+    pub instrument_name: String,
+
+    /// Number of bids and asks requested, the book may have less
+    pub depth: i64,
+
+    /// The actual book data
+    pub data: Vec<Book>,
+}
+
 /// Book received from subscription
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+#[serde(try_from = "RawBook")]
 pub struct Book {
     /// The value is: (price, , )
     ///
@@ -105,8 +151,141 @@ pub struct Book {
     pub asks: Vec<Offer>,
 
     /// The operation time
-    #[serde(rename = "t", deserialize_with = "deserialize_number_from_string")]
+    #[serde(rename = "t")]
     pub time: u64,
+
+    /// The last update time, not present in every payload
+    #[serde(rename = "tt", skip_serializing_if = "Option::is_none")]
+    pub update_time: Option<u64>,
+}
+
+/// Book as sent by the exchange. Subscriptions carry the time in `t` but some
+/// snapshots only have `tt`
+#[derive(Deserialize)]
+struct RawBook {
+    bids: Vec<Offer>,
+    asks: Vec<Offer>,
+    #[serde(default, deserialize_with = "deserialize_option_number_from_string")]
+    t: Option<u64>,
+    #[serde(default, deserialize_with = "deserialize_option_number_from_string")]
+    tt: Option<u64>,
+}
+
+impl TryFrom<RawBook> for Book {
+    type Error = String;
+
+    fn try_from(raw: RawBook) -> Result<Self, Self::Error> {
+        let time = raw.t.or(raw.tt).ok_or("missing field `t`")?;
+        Ok(Book {
+            bids: raw.bids,
+            asks: raw.asks,
+            time,
+            update_time: raw.tt,
+        })
+    }
+}
+
+impl Book {
+    /// Levels of `side`, in the order of the exchange
+    pub fn offers(&self, side: BookSide) -> &[Offer] {
+        match side {
+            BookSide::Bid => &self.bids,
+            BookSide::Ask => &self.asks,
+        }
+    }
+}
+
+/// Book of an instrument kept up to date with the `book` results, the levels of
+/// each side sorted best price first
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct OrderBook {
+    pub instrument_name: String,
+
+    bids: Vec<Offer>,
+
+    asks: Vec<Offer>,
+
+    /// Time of the last book applied
+    pub time: u64,
+}
+
+impl OrderBook {
+    pub fn new(instrument_name: &str) -> OrderBook {
+        OrderBook {
+            instrument_name: instrument_name.to_owned(),
+            ..Default::default()
+        }
+    }
+
+    /// Replace the levels with the ones of `book`, the exchange sends the whole book every time.
+    /// Books older than the last one applied are ignored
+    pub fn apply(&mut self, book: &Book) {
+        if book.time < self.time {
+            return;
+        }
+        for side in [BookSide::Bid, BookSide::Ask] {
+            let mut levels = book.offers(side).to_vec();
+            levels.sort_by(|a, b| match side {
+                BookSide::Bid => b.price.total_cmp(&a.price),
+                BookSide::Ask => a.price.total_cmp(&b.price),
+            });
+            match side {
+                BookSide::Bid => self.bids = levels,
+                BookSide::Ask => self.asks = levels,
+            }
+        }
+        self.time = book.time;
+    }
+
+    /// Levels of `side`, best price first
+    pub fn levels(&self, side: BookSide) -> &[Offer] {
+        match side {
+            BookSide::Bid => &self.bids,
+            BookSide::Ask => &self.asks,
+        }
+    }
+
+    pub fn best(&self, side: BookSide) -> Option<&Offer> {
+        self.levels(side).first()
+    }
+
+    /// Quantity of the level at `price` on `side`, 0 if there is no such level
+    pub fn depth_at(&self, side: BookSide, price: f64) -> f64 {
+        self.levels(side)
+            .iter()
+            .filter(|offer| offer.price == price)
+            .map(|offer| offer.quantity)
+            .sum()
+    }
+
+    /// Quantity of the levels at `price` or better on `side`
+    pub fn depth_through(&self, side: BookSide, price: f64) -> f64 {
+        self.levels(side)
+            .iter()
+            .take_while(|offer| offer.price == price || side.is_better(offer.price, price))
+            .map(|offer| offer.quantity)
+            .sum()
+    }
+
+    /// Best ask minus best bid
+    pub fn spread(&self) -> Option<f64> {
+        Some(self.best(BookSide::Ask)?.price - self.best(BookSide::Bid)?.price)
+    }
+
+    pub fn mid_price(&self) -> Option<f64> {
+        Some((self.best(BookSide::Ask)?.price + self.best(BookSide::Bid)?.price) / 2.0)
+    }
+}
+
+/// Order book with the latest book of the result
+impl From<&BookResult> for OrderBook {
+    fn from(result: &BookResult) -> Self {
+        let mut order_book = OrderBook::new(&result.instrument_name);
+        for book in &result.data {
+            order_book.apply(book);
+        }
+        order_book
+    }
 }
 
 pub fn book(instrument_name: &str, depth: i32) -> String {
@@ -118,6 +297,129 @@ mod tests {
     use super::*;
     use serde_json::from_str;
 
+    #[test]
+    fn check_string_and_number_elements() {
+        let expected = Offer {
+            price: 11746.488,
+            quantity: 128.0,
+            amount: 8.0,
+            ..Default::default()
+        };
+        assert_eq!(from_str::<Offer>("[\"11746.488\", \"128\", \"8\"]").unwrap(), expected);
+        assert_eq!(from_str::<Offer>("[11746.488, 128.0, 8]").unwrap(), expected);
+        assert!(from_str::<Offer>("[\"11746.488\", true, \"8\"]").is_err());
+    }
+
+    #[test]
+    fn check_extra_level_elements() {
+        assert!(from_str::<Offer>("[\"50126.5\", \"0.4\", \"2\", \"1613580715768\"]").is_err());
+        assert!(from_str::<Offer>("[\"50126.5\", \"0.4\"]").is_err());
+    }
+
+    #[test]
+    fn check_snapshot_structure() {
+        let json = "{
+            \"instrument_name\": \"ETH_CRO\",
+            \"depth\": 150,
+            \"data\": [
+                {
+                    \"bids\": [[\"11746.488\", \"128\", \"8\"]],
+                    \"asks\": [[\"11747.488\", \"201\", \"12\"], [\"11748.0\", \"1.5\", \"1\"]],
+                    \"tt\": 1587523078844
+                }
+            ]
+        }";
+        let snapshot = from_str::<BookSnapshotResult>(json).unwrap();
+        assert_eq!(snapshot.instrument_name, "ETH_CRO");
+        assert_eq!(snapshot.depth, 150);
+
+        // Less levels than requested
+        let data = &snapshot.data[0];
+        assert_eq!(data.bids.len(), 1);
+        assert_eq!(data.asks.len(), 2);
+        assert_eq!(data.time, 1587523078844);
+        assert_eq!(data.update_time, Some(1587523078844));
+    }
+
+    #[test]
+    fn check_both_times() {
+        let json = "{\"bids\": [], \"asks\": [], \"t\": 1587523078844, \"tt\": 1587523078800}";
+        let book = from_str::<Book>(json).unwrap();
+        assert_eq!(book.time, 1587523078844);
+        assert_eq!(book.update_time, Some(1587523078800));
+
+        assert!(from_str::<Book>("{\"bids\": [], \"asks\": []}").is_err());
+    }
+
+    #[test]
+    fn check_round_trip() {
+        let frame = "{
+            \"instrument_name\": \"BTC_USDT\",
+            \"subscription\": \"book.BTC_USDT.10\",
+            \"depth\": 10,
+            \"data\": [
+                {
+                    \"bids\": [[\"50126.000000\", \"0.400000\", \"2\"], [\"30080\", \"0.00001234\", \"12\"]],
+                    \"asks\": [[\"51204.48000\", \"0.03125\", \"1\"]],
+                    \"t\": 1654780033786,
+                    \"tt\": 1654780033755
+                }
+            ]
+        }";
+        let book_result = from_str::<BookResult>(frame).unwrap();
+        let bids = &book_result.data[0].bids;
+        assert_eq!(bids[0].price, 50126.0);
+        assert_eq!(bids[0].price_raw.as_deref(), Some("50126.000000"));
+        assert_eq!(bids[1].price_raw, None);
+
+        // The same bytes as the frame, but for the key order and the spaces
+        let serialized = serde_json::to_string(&book_result).unwrap();
+        assert_eq!(
+            from_str::<serde_json::Value>(&serialized).unwrap(),
+            from_str::<serde_json::Value>(frame).unwrap()
+        );
+        assert_eq!(from_str::<BookResult>(&serialized).unwrap(), book_result);
+
+        // Offers built by hand use the shortest text
+        let offer = Offer { price: 50126.0, quantity: 0.4, amount: 2.0, ..Default::default() };
+        assert_eq!(serde_json::to_string(&offer).unwrap(), "[\"50126\",\"0.4\",\"2\"]");
+    }
+
+    fn offer(price: f64, quantity: f64) -> Offer {
+        Offer { price, quantity, amount: 1.0, ..Default::default() }
+    }
+
+    #[test]
+    fn check_order_book() {
+        let book = Book {
+            bids: vec![offer(99.0, 2.0), offer(100.0, 1.0), offer(98.5, 4.0)],
+            asks: vec![offer(101.0, 3.0), offer(100.5, 0.5)],
+            time: 1654780033786,
+            update_time: None,
+        };
+        assert_eq!(book.offers(BookSide::Ask), &book.asks[..]);
+
+        let mut order_book = OrderBook::new("BTC_USDT");
+        order_book.apply(&book);
+        assert_eq!(order_book.best(BookSide::Bid), Some(&offer(100.0, 1.0)));
+        assert_eq!(order_book.best(BookSide::Ask), Some(&offer(100.5, 0.5)));
+        assert_eq!(order_book.levels(BookSide::Bid)[2], offer(98.5, 4.0));
+        assert_eq!(order_book.spread(), Some(0.5));
+        assert_eq!(order_book.mid_price(), Some(100.25));
+
+        assert_eq!(order_book.depth_at(BookSide::Bid, 99.0), 2.0);
+        assert_eq!(order_book.depth_at(BookSide::Bid, 99.5), 0.0);
+        assert_eq!(order_book.depth_through(BookSide::Bid, 99.0), 3.0);
+        assert_eq!(order_book.depth_through(BookSide::Ask, 101.0), 3.5);
+        assert_eq!(order_book.depth_through(BookSide::Ask, 100.0), 0.0);
+
+        // An older book does not replace the levels
+        let old = Book { bids: vec![], asks: vec![], time: 1654780033000, update_time: None };
+        order_book.apply(&old);
+        assert_eq!(order_book.time, 1654780033786);
+        assert_eq!(order_book.levels(BookSide::Ask).len(), 2);
+    }
+
     #[test]
     fn check_structure() {
         let json = "{ \"instrument_name\": \"ETH_CRO\",
@@ -181,6 +483,7 @@ mod tests {
                 price: 11746.488,
                 quantity: 128.0,
                 amount: 8.0,
+                ..Default::default()
             }
         );
         assert_eq!(
@@ -189,6 +492,7 @@ mod tests {
                 price: 22.488,
                 quantity: 22128.1,
                 amount: 228.0,
+                ..Default::default()
             }
         );
         assert_eq!(data.asks.len(), 1);
@@ -198,6 +502,7 @@ mod tests {
                 price: 11747.488,
                 quantity: 201.0,
                 amount: 12.0,
+                ..Default::default()
             }
         );
         assert_eq!(data.time, 1587523078844);
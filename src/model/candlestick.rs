@@ -1,9 +1,13 @@
-use serde::{Serialize, Deserialize};
+use chrono::{DateTime, Datelike, TimeZone, Utc};
+use serde::{Serialize, Serializer, Deserialize, ser::SerializeStruct};
 use serde_aux::prelude::deserialize_number_from_string;
 use std::fmt;
 
+use super::book::AsString;
+use super::string_number::WithText;
+
 // Main container of a candlestick
-#[derive(Serialize,Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct CandlestickResult {
     /// Just the instrument name
     pub instrument_name: String,
@@ -30,38 +34,102 @@ pub struct CandlestickResult {
     pub data: Vec<Candlestick>
 }
 
-/// Candlestick received from subscription
-#[derive(Serialize, Deserialize, Debug)]
+/// Candlestick received from subscription.
+///
+/// The prices and the volume are `f64`, an `f32` cannot hold every digit the exchange
+/// sends, like the volume `336.452694`
+#[derive(Deserialize, Debug, Clone, PartialEq, Default)]
+#[serde(from = "RawCandlestick")]
 pub struct Candlestick {
 
     /// Open price
-    #[serde(rename = "o", deserialize_with = "deserialize_number_from_string")]
-    pub open: f32,
+    pub open: f64,
     
     /// Close price
-    #[serde(rename = "c", deserialize_with = "deserialize_number_from_string")]
-    pub close: f32,
+    pub close: f64,
 
     /// Highest price
-    #[serde(rename = "h", deserialize_with = "deserialize_number_from_string")]
-    pub high: f32,
+    pub high: f64,
 
     /// Lowest price
-    #[serde(rename = "l", deserialize_with = "deserialize_number_from_string")]
-    pub low: f32,
+    pub low: f64,
 
     /// Volume
-    #[serde(rename = "v", deserialize_with = "deserialize_number_from_string")]
-    pub volume: f32,
+    pub volume: f64,
 
-    #[serde(rename = "ut", deserialize_with = "deserialize_number_from_string")]
     pub update_time: u64,
 
-    #[serde(rename = "t", deserialize_with = "deserialize_number_from_string")]
     pub start_time: u64,
+
+    /// Text of the open price as sent by the exchange, only when it is not the usual
+    /// one like `"162.030000"`. Written back when serializing
+    pub open_raw: Option<String>,
+
+    /// Text of the close price, like `open_raw`
+    pub close_raw: Option<String>,
+
+    /// Text of the highest price, like `open_raw`
+    pub high_raw: Option<String>,
+
+    /// Text of the lowest price, like `open_raw`
+    pub low_raw: Option<String>,
+
+    /// Text of the volume, like `open_raw`
+    pub volume_raw: Option<String>,
+}
+
+/// Candlestick as sent by the exchange, the numbers with their text
+#[derive(Deserialize)]
+struct RawCandlestick {
+    o: WithText,
+    c: WithText,
+    h: WithText,
+    l: WithText,
+    v: WithText,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    ut: u64,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    t: u64,
+}
+
+impl From<RawCandlestick> for Candlestick {
+    fn from(raw: RawCandlestick) -> Self {
+        Candlestick {
+            open: raw.o.value,
+            close: raw.c.value,
+            high: raw.h.value,
+            low: raw.l.value,
+            volume: raw.v.value,
+            update_time: raw.ut,
+            start_time: raw.t,
+            open_raw: raw.o.text,
+            close_raw: raw.c.text,
+            high_raw: raw.h.text,
+            low_raw: raw.l.text,
+            volume_raw: raw.v.text,
+        }
+    }
+}
+
+/// The numbers are written as strings like the exchange, with their original text if any
+impl Serialize for Candlestick {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut state = serializer.serialize_struct("Candlestick", 7)?;
+        state.serialize_field("o", &AsString(&self.open, self.open_raw.as_deref()))?;
+        state.serialize_field("c", &AsString(&self.close, self.close_raw.as_deref()))?;
+        state.serialize_field("h", &AsString(&self.high, self.high_raw.as_deref()))?;
+        state.serialize_field("l", &AsString(&self.low, self.low_raw.as_deref()))?;
+        state.serialize_field("v", &AsString(&self.volume, self.volume_raw.as_deref()))?;
+        state.serialize_field("ut", &self.update_time)?;
+        state.serialize_field("t", &self.start_time)?;
+        state.end()
+    }
 }
 
-#[derive(Serialize,Deserialize, Debug)]
+#[derive(Serialize,Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum TimeFrame {
     #[serde(rename = "1m")]
     OneMinute,
@@ -119,11 +187,159 @@ impl fmt::Display for TimeFrame {
     }
 }
 
+const MINUTE: u64 = 60_000;
+const HOUR: u64 = 60 * MINUTE;
+const DAY: u64 = 24 * HOUR;
+/// The epoch was a thursday, weeks start on monday
+const WEEK_OFFSET: u64 = 4 * DAY;
+
+impl TimeFrame {
+    /// Start time (millis since epoch) of the candle of this time frame containing `time`.
+    /// A week starting before the epoch is clamped to 0
+    pub fn bucket_start(&self, time: u64) -> u64 {
+        let floor = |size: u64, offset: u64| {
+            time.saturating_sub((time % size + size - offset % size) % size)
+        };
+        match *self {
+            TimeFrame::OneMinute => floor(MINUTE, 0),
+            TimeFrame::FiveMinutes => floor(5 * MINUTE, 0),
+            TimeFrame::FiteenMinutes => floor(15 * MINUTE, 0),
+            TimeFrame::ThirtyMinutes => floor(30 * MINUTE, 0),
+            TimeFrame::OneHour => floor(HOUR, 0),
+            TimeFrame::FourHours => floor(4 * HOUR, 0),
+            TimeFrame::SixHours => floor(6 * HOUR, 0),
+            TimeFrame::TwelveHours => floor(12 * HOUR, 0),
+            TimeFrame::OneDay => floor(DAY, 0),
+            TimeFrame::OneWeek => floor(7 * DAY, WEEK_OFFSET),
+            TimeFrame::TwoWeeks => floor(14 * DAY, WEEK_OFFSET),
+            TimeFrame::OneMonth => {
+                let date = DateTime::from_timestamp_millis(time as i64).unwrap_or_default();
+                Utc.with_ymd_and_hms(date.year(), date.month(), 1, 0, 0, 0)
+                    .single()
+                    .map(|start| start.timestamp_millis() as u64)
+                    .unwrap_or(time)
+            }
+        }
+    }
+}
+
 pub fn candlestick(time_frame: TimeFrame, instrument_name: &str) -> String {
     format!("candlestick.{time_frame}.{instrument_name}")
 }
 
+/// Merges a stream of fine candles (usually 1m) into candles of a bigger time frame.
+///
+/// The exchange sends the open candle again every time it changes, so a candle with
+/// the same `start_time` as the previous one replaces it instead of being added.
+/// Gaps are tolerated: missing candles are just not part of the merge, and time frames
+/// without any candle do not produce an empty bar.
+#[derive(Debug, Clone)]
+pub struct CandleAggregator {
+    /// Target time frame
+    time_frame: TimeFrame,
+
+    /// Start time of the bar being built
+    bucket: Option<u64>,
+
+    /// Merge of the finished source candles of the bar being built
+    closed: Option<Candlestick>,
+
+    /// Latest version of the source candle still open
+    open: Option<Candlestick>,
+}
+
+impl CandleAggregator {
+    pub fn new(time_frame: TimeFrame) -> CandleAggregator {
+        CandleAggregator {
+            time_frame,
+            bucket: None,
+            closed: None,
+            open: None,
+        }
+    }
+
+    /// Target time frame of the aggregated candles
+    pub fn time_frame(&self) -> TimeFrame {
+        self.time_frame
+    }
+
+    /// True if `candle` is a new version of the source candle still open
+    pub fn is_update(&self, candle: &Candlestick) -> bool {
+        self.open
+            .as_ref()
+            .is_some_and(|open| open.start_time == candle.start_time)
+    }
+
+    /// Add a source candle. Returns the completed bar when `candle` starts a new one.
+    /// Candles older than the open one are ignored.
+    pub fn push(&mut self, candle: &Candlestick) -> Option<Candlestick> {
+        if let Some(open) = self.open.as_ref() {
+            if candle.start_time < open.start_time {
+                return None;
+            }
+            if candle.start_time == open.start_time {
+                self.open = Some(candle.clone());
+                return None;
+            }
+        }
+
+        let bucket = self.time_frame.bucket_start(candle.start_time);
+        if self.bucket == Some(bucket) {
+            self.closed = merge(self.closed.take(), self.open.take());
+            self.open = Some(candle.clone());
+            None
+        } else {
+            let completed = self.current();
+            self.bucket = Some(bucket);
+            self.closed = None;
+            self.open = Some(candle.clone());
+            completed
+        }
+    }
+
+    /// The bar being built, including the open source candle
+    pub fn current(&self) -> Option<Candlestick> {
+        let mut bar = merge(self.closed.clone(), self.open.clone())?;
+        bar.start_time = self.bucket?;
+        Some(bar)
+    }
+}
+
+/// Merge two consecutive candles, `first` being the older one
+fn merge(first: Option<Candlestick>, second: Option<Candlestick>) -> Option<Candlestick> {
+    match (first, second) {
+        (Some(first), Some(second)) => {
+            let (high, high_raw) = if second.high > first.high {
+                (second.high, second.high_raw)
+            } else {
+                (first.high, first.high_raw)
+            };
+            let (low, low_raw) = if second.low < first.low {
+                (second.low, second.low_raw)
+            } else {
+                (first.low, first.low_raw)
+            };
+            Some(Candlestick {
+                open: first.open,
+                close: second.close,
+                high,
+                low,
+                volume: first.volume + second.volume,
+                update_time: first.update_time.max(second.update_time),
+                start_time: first.start_time,
+                open_raw: first.open_raw,
+                close_raw: second.close_raw,
+                high_raw,
+                low_raw,
+                volume_raw: None,
+            })
+        }
+        (first, second) => first.or(second),
+    }
+}
+
 #[cfg(test)]
+#[allow(clippy::excessive_precision)]
 mod tests {
     use super::*;
     use serde_json::from_str;
@@ -175,4 +391,144 @@ mod tests {
         assert_eq!(data.update_time, 1589443242000);
         
     }
-}
\ No newline at end of file
+
+    /// 1m candle starting `minute` minutes after 2022-11-28 00:00 UTC
+    fn minute_candle(minute: u64) -> Candlestick {
+        let price = 100.0 + minute as f64;
+        Candlestick {
+            open: price,
+            close: price + 0.5,
+            high: price + 1.0,
+            low: price - 1.0,
+            volume: 2.0,
+            update_time: 1669593600000 + minute * 60_000 + 59_000,
+            start_time: 1669593600000 + minute * 60_000,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn check_round_trip() {
+        let frame = "{
+            \"instrument_name\": \"ETH_CRO\",
+            \"subscription\": \"candlestick.1m.ETH_CRO\",
+            \"interval\": \"1m\",
+            \"data\": [
+              {
+                \"o\": \"162.030000\",
+                \"c\": \"162.04\",
+                \"h\": \"162.10\",
+                \"l\": \"161.98\",
+                \"v\": \"336.452694\",
+                \"t\": 1589443241000,
+                \"ut\": 1589443242000
+              }
+            ]
+          }";
+        let candlestick_result = from_str::<CandlestickResult>(frame).unwrap();
+        let candle = &candlestick_result.data[0];
+        assert_eq!(candle.open, 162.03);
+        assert_eq!(candle.open_raw.as_deref(), Some("162.030000"));
+        assert_eq!(candle.high_raw.as_deref(), Some("162.10"));
+        assert_eq!(candle.close_raw, None);
+
+        // The same bytes as the frame, but for the key order and the spaces
+        let serialized = serde_json::to_string(&candlestick_result).unwrap();
+        assert_eq!(
+            from_str::<serde_json::Value>(&serialized).unwrap(),
+            from_str::<serde_json::Value>(frame).unwrap()
+        );
+        assert_eq!(from_str::<CandlestickResult>(&serialized).unwrap(), candlestick_result);
+    }
+
+    #[test]
+    fn check_bucket_start() {
+        // 2022-11-28 10:37:12 UTC, a monday
+        let time = 1669631832000;
+        assert_eq!(TimeFrame::OneMinute.bucket_start(time), 1669631820000);
+        assert_eq!(TimeFrame::FiveMinutes.bucket_start(time), 1669631700000);
+        assert_eq!(TimeFrame::OneHour.bucket_start(time), 1669629600000);
+        assert_eq!(TimeFrame::OneDay.bucket_start(time), 1669593600000);
+        assert_eq!(TimeFrame::OneWeek.bucket_start(time), 1669593600000);
+        // 2022-11-01 00:00 UTC
+        assert_eq!(TimeFrame::OneMonth.bucket_start(time), 1667260800000);
+
+        // The first days of the epoch belong to a week starting before it
+        assert_eq!(TimeFrame::OneWeek.bucket_start(0), 0);
+        assert_eq!(TimeFrame::TwoWeeks.bucket_start(DAY), 0);
+        assert_eq!(TimeFrame::OneWeek.bucket_start(3 * DAY), 0);
+        assert_eq!(TimeFrame::OneWeek.bucket_start(5 * DAY), 4 * DAY);
+        assert_eq!(TimeFrame::OneMinute.bucket_start(u64::MAX), u64::MAX - u64::MAX % MINUTE);
+    }
+
+    #[test]
+    fn check_aggregation_with_gap() {
+        let mut five_minutes = CandleAggregator::new(TimeFrame::FiveMinutes);
+        let mut one_hour = CandleAggregator::new(TimeFrame::OneHour);
+        let mut completed = Vec::new();
+
+        // A full hour with minutes 20 to 29 missing
+        for minute in (0..60).filter(|minute| !(20..30).contains(minute)) {
+            let candle = minute_candle(minute);
+            completed.extend(five_minutes.push(&candle));
+            assert!(one_hour.push(&candle).is_none());
+        }
+
+        let starts: Vec<u64> = completed.iter().map(|bar| (bar.start_time - 1669593600000) / 60_000).collect();
+        assert_eq!(starts, vec![0, 5, 10, 15, 30, 35, 40, 45, 50]);
+
+        let bar = &completed[1];
+        assert_eq!(bar.open, 105.0);
+        assert_eq!(bar.close, 109.5);
+        assert_eq!(bar.high, 110.0);
+        assert_eq!(bar.low, 104.0);
+        assert_eq!(bar.volume, 10.0);
+        assert_eq!(bar.update_time, minute_candle(9).update_time);
+
+        // The bar after the gap only contains its own minutes
+        let bar = &completed[4];
+        assert_eq!(bar.open, 130.0);
+        assert_eq!(bar.close, 134.5);
+        assert_eq!(bar.volume, 10.0);
+
+        let last = five_minutes.current().unwrap();
+        assert_eq!(last.start_time, minute_candle(55).start_time);
+        assert_eq!(last.close, 159.5);
+
+        let hour = one_hour.current().unwrap();
+        assert_eq!(hour.start_time, 1669593600000);
+        assert_eq!(hour.open, 100.0);
+        assert_eq!(hour.close, 159.5);
+        assert_eq!(hour.high, 160.0);
+        assert_eq!(hour.low, 99.0);
+        assert_eq!(hour.volume, 100.0);
+
+        // The next hour completes the bar
+        assert_eq!(one_hour.push(&minute_candle(60)), Some(hour));
+    }
+
+    #[test]
+    fn check_updates_of_open_candle() {
+        let mut aggregator = CandleAggregator::new(TimeFrame::FiveMinutes);
+        aggregator.push(&minute_candle(0));
+
+        let mut update = minute_candle(1);
+        assert!(!aggregator.is_update(&update));
+        aggregator.push(&update);
+
+        update.close = 90.0;
+        update.low = 89.0;
+        update.volume = 3.0;
+        assert!(aggregator.is_update(&update));
+        assert!(aggregator.push(&update).is_none());
+
+        // Stale candles are ignored
+        assert!(aggregator.push(&minute_candle(0)).is_none());
+
+        let bar = aggregator.current().unwrap();
+        assert_eq!(bar.open, 100.0);
+        assert_eq!(bar.close, 90.0);
+        assert_eq!(bar.low, 89.0);
+        assert_eq!(bar.volume, 5.0);
+    }
+}
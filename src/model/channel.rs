@@ -0,0 +1,208 @@
+use serde::de::{value, IntoDeserializer};
+use serde::Deserialize;
+use std::fmt;
+use std::str::FromStr;
+
+use super::candlestick::TimeFrame;
+
+/// A subscription channel, the `subscription` field of the results.
+///
+/// `to_string` gives the name used to subscribe and `parse` reads it back.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Channel {
+    Trade { instrument_name: String },
+    Candlestick { time_frame: TimeFrame, instrument_name: String },
+    Ticker { instrument_name: String },
+    /// The tickers of every instrument, `ticker` without instrument name
+    AllTickers,
+    Book { instrument_name: String, depth: i32 },
+    OtcBook { instrument_name: String },
+    Index { instrument_name: String },
+    Mark { instrument_name: String },
+    Settlement { instrument_name: String },
+    Funding { instrument_name: String },
+    EstimatedFunding { instrument_name: String },
+    UserOrder { instrument_name: String },
+    UserTrade { instrument_name: String },
+    UserBalance,
+    UserPositions,
+    UserPositionBalance,
+}
+
+impl fmt::Display for Channel {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Channel::Trade { instrument_name } => write!(f, "trade.{instrument_name}"),
+            Channel::Candlestick { time_frame, instrument_name } => write!(f, "candlestick.{time_frame}.{instrument_name}"),
+            Channel::Ticker { instrument_name } => write!(f, "ticker.{instrument_name}"),
+            Channel::AllTickers => write!(f, "ticker"),
+            Channel::Book { instrument_name, depth } => write!(f, "book.{instrument_name}.{depth}"),
+            Channel::OtcBook { instrument_name } => write!(f, "otc_book.{instrument_name}"),
+            Channel::Index { instrument_name } => write!(f, "index.{instrument_name}"),
+            Channel::Mark { instrument_name } => write!(f, "mark.{instrument_name}"),
+            Channel::Settlement { instrument_name } => write!(f, "settlement.{instrument_name}"),
+            Channel::Funding { instrument_name } => write!(f, "funding.{instrument_name}"),
+            Channel::EstimatedFunding { instrument_name } => write!(f, "estimatedfunding.{instrument_name}"),
+            Channel::UserOrder { instrument_name } => write!(f, "user.order.{instrument_name}"),
+            Channel::UserTrade { instrument_name } => write!(f, "user.trade.{instrument_name}"),
+            Channel::UserBalance => write!(f, "user.balance"),
+            Channel::UserPositions => write!(f, "user.positions"),
+            Channel::UserPositionBalance => write!(f, "user.position_balance"),
+        }
+    }
+}
+
+/// The first part of the channels, before the first `.`
+const KINDS: [&str; 11] = ["trade", "candlestick", "ticker", "book", "otc_book", "index", "mark", "settlement", "funding", "estimatedfunding", "user"];
+
+/// The error is the reason why `subscription` is not a channel
+impl FromStr for Channel {
+    type Err = String;
+
+    fn from_str(subscription: &str) -> Result<Self, Self::Err> {
+        let (kind, rest) = subscription.split_once('.').unwrap_or((subscription, ""));
+        if !KINDS.contains(&kind) {
+            return Err("unknown channel".to_owned());
+        }
+        if subscription == "ticker" {
+            return Ok(Channel::AllTickers);
+        }
+        if rest.is_empty() {
+            return Err("missing instrument name".to_owned());
+        }
+        let instrument_name = rest.to_owned();
+        Ok(match kind {
+            "trade" => Channel::Trade { instrument_name },
+            "ticker" => Channel::Ticker { instrument_name },
+            "otc_book" => Channel::OtcBook { instrument_name },
+            "index" => Channel::Index { instrument_name },
+            "mark" => Channel::Mark { instrument_name },
+            "settlement" => Channel::Settlement { instrument_name },
+            "funding" => Channel::Funding { instrument_name },
+            "estimatedfunding" => Channel::EstimatedFunding { instrument_name },
+            "book" => {
+                let (instrument_name, depth) = rest.rsplit_once('.').ok_or("missing depth, like book.ETH_CRO.10")?;
+                if instrument_name.is_empty() {
+                    return Err("missing instrument name".to_owned());
+                }
+                Channel::Book {
+                    instrument_name: instrument_name.to_owned(),
+                    depth: depth.parse().ok().filter(|depth| *depth > 0).ok_or_else(|| format!("the depth \"{depth}\" is not a positive number"))?,
+                }
+            }
+            "candlestick" => {
+                let (time_frame, instrument_name) = rest.split_once('.').ok_or("missing time frame, like candlestick.1m.ETH_CRO")?;
+                if instrument_name.is_empty() {
+                    return Err("missing instrument name".to_owned());
+                }
+                let deserializer = IntoDeserializer::<value::Error>::into_deserializer(time_frame);
+                Channel::Candlestick {
+                    time_frame: TimeFrame::deserialize(deserializer).map_err(|_| format!("unknown time frame \"{time_frame}\""))?,
+                    instrument_name: instrument_name.to_owned(),
+                }
+            }
+            "user" => match rest.split_once('.') {
+                Some(("order", instrument_name)) if !instrument_name.is_empty() => Channel::UserOrder {
+                    instrument_name: instrument_name.to_owned(),
+                },
+                Some(("trade", instrument_name)) if !instrument_name.is_empty() => Channel::UserTrade {
+                    instrument_name: instrument_name.to_owned(),
+                },
+                None if rest == "balance" => Channel::UserBalance,
+                None if rest == "positions" => Channel::UserPositions,
+                None if rest == "position_balance" => Channel::UserPositionBalance,
+                _ => return Err("unknown user channel".to_owned()),
+            },
+            _ => unreachable!("every kind is handled"),
+        })
+    }
+}
+
+/// Check `subscription` before subscribing to it. The user channels not modelled by
+/// `Channel` are accepted, and so are the unknown channels if `allow_unknown`
+pub(crate) fn check(subscription: &str, allow_unknown: bool) -> Result<(), String> {
+    match subscription.split_once('.') {
+        Some(("user", rest)) if !rest.is_empty() => Ok(()),
+        Some((kind, _)) if allow_unknown && !KINDS.contains(&kind) => Ok(()),
+        None if allow_unknown && !KINDS.contains(&subscription) => Ok(()),
+        _ => subscription.parse::<Channel>().map(|_| ()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model;
+
+    #[test]
+    fn check_round_trip() {
+        let subscriptions = [
+            model::trade("ETH_CRO"),
+            model::candlestick(TimeFrame::OneDay, "ETH_CRO"),
+            model::ticker("ETH_CRO"),
+            model::all_tickers(),
+            model::book("ETH_CRO", 150),
+            model::otc_book("ETH_CRO"),
+            model::index("BTCUSD-INDEX"),
+            model::mark("BTCUSD-PERP"),
+            model::settlement("BTCUSD-PERP"),
+            model::funding("BTCUSD-PERP"),
+            model::estimated_funding("BTCUSD-PERP"),
+            model::user_order("ETH_CRO"),
+            model::user_trade("ETH_CRO"),
+            model::balance(),
+            model::positions(),
+            model::position_balance(),
+        ];
+        for subscription in subscriptions {
+            let channel = subscription.parse::<Channel>().unwrap();
+            assert_eq!(channel.to_string(), subscription);
+        }
+    }
+
+    #[test]
+    fn check_fields() {
+        assert_eq!(
+            "book.BTC_USD.PERP.10".parse::<Channel>(),
+            Ok(Channel::Book { instrument_name: "BTC_USD.PERP".to_owned(), depth: 10 })
+        );
+        assert_eq!(
+            "candlestick.5m.BTC_USDT".parse::<Channel>(),
+            Ok(Channel::Candlestick { time_frame: TimeFrame::FiveMinutes, instrument_name: "BTC_USDT".to_owned() })
+        );
+        assert_eq!("ticker".parse::<Channel>(), Ok(Channel::AllTickers));
+    }
+
+    #[test]
+    fn check_reasons() {
+        let reason = |subscription: &str| subscription.parse::<Channel>().unwrap_err();
+        assert_eq!(reason("trades.ETH_CRO"), "unknown channel");
+        assert_eq!(reason("index"), "missing instrument name");
+        assert_eq!(reason("ticker."), "missing instrument name");
+        assert_eq!(reason("book.ETH_CRO.1O"), "the depth \"1O\" is not a positive number");
+        assert_eq!(reason("book.ETH_CRO.0"), "the depth \"0\" is not a positive number");
+        assert_eq!(reason("candlestick.2m.ETH_CRO"), "unknown time frame \"2m\"");
+        assert_eq!(reason("user.orders"), "unknown user channel");
+        assert_eq!(reason("user.trade."), "unknown user channel");
+    }
+
+    #[test]
+    fn check_unknown_channels() {
+        assert_eq!(check("user.trade.ETH_CRO", false), Ok(()));
+        assert_eq!(check("user.order", false), Ok(()));
+        assert!(check("user.", false).is_err());
+        assert!(check("liquidation.ETH_CRO", false).is_err());
+        assert_eq!(check("liquidation.ETH_CRO", true), Ok(()));
+        assert_eq!(check("liquidation", true), Ok(()));
+
+        // The known channels are checked anyway
+        assert!(check("book.ETH_CRO.deep", true).is_err());
+    }
+
+    #[test]
+    fn check_unknown() {
+        for subscription in ["", "trade", "trade.", "book.ETH_CRO", "book.ETH_CRO.deep", "candlestick.2m.ETH_CRO", "user.order", "user.orders", "user.trade", "orders.ETH_CRO"] {
+            assert!(subscription.parse::<Channel>().is_err(), "{subscription}");
+        }
+    }
+}
@@ -0,0 +1,107 @@
+use serde::{Serialize, Deserialize};
+use std::fmt;
+
+/// Side of a trade or an order
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Side {
+    #[serde(rename = "BUY")]
+    Buy,
+    #[serde(rename = "SELL")]
+    Sell
+}
+
+impl Side {
+    pub fn opposite(self) -> Side {
+        match self {
+            Side::Buy => Side::Sell,
+            Side::Sell => Side::Buy,
+        }
+    }
+
+    /// Side of the book where an order of this side rests
+    pub fn book_side(self) -> BookSide {
+        match self {
+            Side::Buy => BookSide::Bid,
+            Side::Sell => BookSide::Ask,
+        }
+    }
+}
+
+impl fmt::Display for Side {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Side::Buy => write!(f, "BUY"),
+            Side::Sell => write!(f, "SELL"),
+        }
+    }
+}
+
+/// Side of a book, the bids are the buy orders and the asks the sell orders
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum BookSide {
+    Bid,
+    Ask
+}
+
+impl BookSide {
+    pub fn opposite(self) -> BookSide {
+        match self {
+            BookSide::Bid => BookSide::Ask,
+            BookSide::Ask => BookSide::Bid,
+        }
+    }
+
+    /// Side of the orders resting on this side of the book
+    pub fn side(self) -> Side {
+        match self {
+            BookSide::Bid => Side::Buy,
+            BookSide::Ask => Side::Sell,
+        }
+    }
+
+    /// True if `price` is a better price than `other` on this side:
+    /// higher for the bids, lower for the asks
+    pub fn is_better(self, price: f64, other: f64) -> bool {
+        match self {
+            BookSide::Bid => price > other,
+            BookSide::Ask => price < other,
+        }
+    }
+}
+
+impl fmt::Display for BookSide {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            BookSide::Bid => write!(f, "bid"),
+            BookSide::Ask => write!(f, "ask"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::{from_str, to_string};
+
+    #[test]
+    fn check_wire_format() {
+        assert_eq!(from_str::<Side>("\"BUY\"").unwrap(), Side::Buy);
+        assert_eq!(from_str::<Side>("\"SELL\"").unwrap(), Side::Sell);
+        assert!(from_str::<Side>("\"buy\"").is_err());
+        assert_eq!(to_string(&Side::Sell).unwrap(), "\"SELL\"");
+        assert_eq!(Side::Buy.to_string(), "BUY");
+    }
+
+    #[test]
+    fn check_sides() {
+        assert_eq!(Side::Buy.opposite(), Side::Sell);
+        assert_eq!(BookSide::Ask.opposite(), BookSide::Bid);
+        for side in [Side::Buy, Side::Sell] {
+            assert_eq!(side.book_side().side(), side);
+        }
+        assert!(BookSide::Bid.is_better(10.5, 10.0));
+        assert!(BookSide::Ask.is_better(10.0, 10.5));
+        assert!(!BookSide::Ask.is_better(10.0, 10.0));
+    }
+}
@@ -0,0 +1,111 @@
+use serde::{Serialize, Deserialize};
+use serde_aux::prelude::deserialize_number_from_string;
+
+// Main container of a funding rate
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct FundingRateResult {
+    /// Just the instrument name, for example BTCUSD-PERP
+    pub instrument_name: String,
+
+    /// Subscription name used to subscribe this event
+    pub subscription: String,
+
+    /// The actual funding rate data
+    pub data: Vec<FundingRate>
+}
+
+// Main container of an estimated funding rate
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct EstimatedFundingRateResult {
+    /// Just the instrument name, for example BTCUSD-PERP
+    pub instrument_name: String,
+
+    /// Subscription name used to subscribe this event
+    pub subscription: String,
+
+    /// The actual estimated funding rate data
+    pub data: Vec<FundingRate>
+}
+
+/// Funding rate element received from subscription
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct FundingRate {
+    /// Hourly funding rate, negative when shorts pay longs
+    #[serde(rename = "v", deserialize_with = "deserialize_number_from_string")]
+    pub value: f64,
+
+    /// Update time
+    #[serde(rename = "t", deserialize_with = "deserialize_number_from_string")]
+    pub time: u64,
+}
+
+pub fn funding(instrument_name: &str) -> String {
+    format!("funding.{instrument_name}")
+}
+
+pub fn estimated_funding(instrument_name: &str) -> String {
+    format!("estimatedfunding.{instrument_name}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::from_str;
+
+    #[test]
+    fn check_structure() {
+        let json = "{
+            \"instrument_name\": \"BTCUSD-PERP\",
+            \"subscription\": \"funding.BTCUSD-PERP\",
+            \"channel\": \"funding\",
+            \"data\": [
+              {
+                \"v\": \"0.00014\",
+                \"t\": 1624003200000
+              }
+            ]
+          }";
+        let funding_result = from_str::<FundingRateResult>(json).unwrap();
+        assert_eq!(funding_result.instrument_name, "BTCUSD-PERP");
+        assert_eq!(funding_result.subscription, "funding.BTCUSD-PERP");
+        assert_eq!(funding_result.data.len(), 1);
+
+        // The data
+        let data = &funding_result.data[0];
+        assert_eq!(data.value, 0.00014);
+        assert_eq!(data.time, 1624003200000);
+    }
+
+    #[test]
+    fn check_negative_rate() {
+        let json = "{
+            \"instrument_name\": \"BTCUSD-PERP\",
+            \"subscription\": \"estimatedfunding.BTCUSD-PERP\",
+            \"channel\": \"estimatedfunding\",
+            \"data\": [
+              {
+                \"v\": \"-0.000123456789012\",
+                \"t\": 1624003200000
+              },
+              {
+                \"v\": \"-1E-7\",
+                \"t\": 1624006800000
+              }
+            ]
+          }";
+        let funding_result = from_str::<EstimatedFundingRateResult>(json).unwrap();
+        assert_eq!(funding_result.subscription, "estimatedfunding.BTCUSD-PERP");
+        assert_eq!(funding_result.data.len(), 2);
+
+        assert_eq!(funding_result.data[0].value, -0.000123456789012);
+        assert_eq!(funding_result.data[0].value.to_string(), "-0.000123456789012");
+        assert_eq!(funding_result.data[1].value, -0.0000001);
+        assert_eq!(funding_result.data[1].time, 1624006800000);
+    }
+
+    #[test]
+    fn check_channel() {
+        assert_eq!(funding("BTCUSD-PERP"), "funding.BTCUSD-PERP");
+        assert_eq!(estimated_funding("BTCUSD-PERP"), "estimatedfunding.BTCUSD-PERP");
+    }
+}
@@ -0,0 +1,66 @@
+use serde::{Serialize, Deserialize};
+use serde_aux::prelude::deserialize_number_from_string;
+
+// Main container of an index price
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct IndexResult {
+    /// Just the instrument name, for example BTCUSD-INDEX
+    pub instrument_name: String,
+
+    /// Subscription name used to subscribe this event
+    pub subscription: String,
+
+    /// The actual index data
+    pub data: Vec<Index>
+}
+
+/// Index price element received from subscription
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Index {
+    /// Index price
+    #[serde(rename = "v", deserialize_with = "deserialize_number_from_string")]
+    pub value: f64,
+
+    /// Update time
+    #[serde(rename = "t", deserialize_with = "deserialize_number_from_string")]
+    pub time: u64,
+}
+
+pub fn index(instrument_name: &str) -> String {
+    format!("index.{instrument_name}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::from_str;
+
+    #[test]
+    fn check_structure() {
+        let json = "{
+            \"instrument_name\": \"BTCUSD-INDEX\",
+            \"subscription\": \"index.BTCUSD-INDEX\",
+            \"channel\": \"index\",
+            \"data\": [
+              {
+                \"v\": \"51204.48000\",
+                \"t\": 1613580710000
+              }
+            ]
+          }";
+        let index_result = from_str::<IndexResult>(json).unwrap();
+        assert_eq!(index_result.instrument_name, "BTCUSD-INDEX");
+        assert_eq!(index_result.subscription, "index.BTCUSD-INDEX");
+        assert_eq!(index_result.data.len(), 1);
+
+        // The data
+        let data = &index_result.data[0];
+        assert_eq!(data.value, 51204.48);
+        assert_eq!(data.time, 1613580710000);
+    }
+
+    #[test]
+    fn check_channel() {
+        assert_eq!(index("BTCUSD-INDEX"), "index.BTCUSD-INDEX");
+    }
+}
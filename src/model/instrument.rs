@@ -0,0 +1,113 @@
+use serde::{Serialize, Deserialize};
+use serde_aux::prelude::deserialize_option_number_from_string;
+
+/// Result of a `public/get-instruments` request.
+///
+/// The exchange returns every instrument in a single response, there are no pages.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct InstrumentsResult {
+    /// The instruments, called `instruments` in older versions of the api
+    #[serde(alias = "instruments", default)]
+    pub data: Vec<InstrumentInfo>
+}
+
+/// Metadata of an instrument
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct InstrumentInfo {
+    /// Just the instrument name, for example ETH_CRO
+    pub instrument_name: String,
+
+    /// Currency used to price the instrument, for example CRO
+    pub quote_currency: String,
+
+    /// Currency traded, for example ETH
+    pub base_currency: String,
+
+    /// Maximum number of decimals of a price
+    pub price_decimals: u32,
+
+    /// Maximum number of decimals of a quantity
+    pub quantity_decimals: u32,
+
+    /// True if margin trading is enabled
+    pub margin_trading_enabled: bool,
+
+    /// Minimum price increment
+    #[serde(default, deserialize_with = "deserialize_option_number_from_string")]
+    pub price_tick_size: Option<f64>,
+
+    /// Minimum quantity increment
+    #[serde(default, deserialize_with = "deserialize_option_number_from_string")]
+    pub quantity_tick_size: Option<f64>,
+
+    /// Minimum order quantity
+    #[serde(default, deserialize_with = "deserialize_option_number_from_string")]
+    pub min_quantity: Option<f64>,
+
+    /// Maximum order quantity
+    #[serde(default, deserialize_with = "deserialize_option_number_from_string")]
+    pub max_quantity: Option<f64>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::from_str;
+
+    #[test]
+    fn check_structure() {
+        let json = "{
+            \"data\": [
+              {
+                \"instrument_name\": \"ETH_CRO\",
+                \"quote_currency\": \"CRO\",
+                \"base_currency\": \"ETH\",
+                \"price_decimals\": 2,
+                \"quantity_decimals\": 2,
+                \"margin_trading_enabled\": false,
+                \"price_tick_size\": \"0.01\",
+                \"quantity_tick_size\": \"0.01\",
+                \"min_quantity\": \"0.01\",
+                \"max_quantity\": \"10000000\"
+              },
+              {
+                \"instrument_name\": \"BTC_USDT\",
+                \"quote_currency\": \"USDT\",
+                \"base_currency\": \"BTC\",
+                \"price_decimals\": 2,
+                \"quantity_decimals\": 6,
+                \"margin_trading_enabled\": true
+              }
+            ]
+          }";
+        let instruments = from_str::<InstrumentsResult>(json).unwrap();
+        assert_eq!(instruments.data.len(), 2);
+
+        let eth = &instruments.data[0];
+        assert_eq!(eth.instrument_name, "ETH_CRO");
+        assert_eq!(eth.quote_currency, "CRO");
+        assert_eq!(eth.base_currency, "ETH");
+        assert_eq!(eth.price_decimals, 2);
+        assert_eq!(eth.quantity_decimals, 2);
+        assert!(!eth.margin_trading_enabled);
+        assert_eq!(eth.price_tick_size, Some(0.01));
+        assert_eq!(eth.quantity_tick_size, Some(0.01));
+        assert_eq!(eth.min_quantity, Some(0.01));
+        assert_eq!(eth.max_quantity, Some(10000000.0));
+
+        let btc = &instruments.data[1];
+        assert_eq!(btc.instrument_name, "BTC_USDT");
+        assert_eq!(btc.quantity_decimals, 6);
+        assert!(btc.margin_trading_enabled);
+        assert_eq!(btc.min_quantity, None);
+    }
+
+    #[test]
+    fn check_legacy_and_empty() {
+        let instruments = from_str::<InstrumentsResult>("{\"instruments\": []}").unwrap();
+        assert!(instruments.data.is_empty());
+
+        let instruments = from_str::<InstrumentsResult>("{}").unwrap();
+        assert!(instruments.data.is_empty());
+    }
+}
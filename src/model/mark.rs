@@ -0,0 +1,66 @@
+use serde::{Serialize, Deserialize};
+use serde_aux::prelude::deserialize_number_from_string;
+
+// Main container of a mark price
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct MarkPriceResult {
+    /// Just the instrument name, for example BTCUSD-PERP
+    pub instrument_name: String,
+
+    /// Subscription name used to subscribe this event
+    pub subscription: String,
+
+    /// The actual mark price data
+    pub data: Vec<MarkPrice>
+}
+
+/// Mark price element received from subscription
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct MarkPrice {
+    /// Mark price
+    #[serde(rename = "v", deserialize_with = "deserialize_number_from_string")]
+    pub value: f64,
+
+    /// Update time
+    #[serde(rename = "t", deserialize_with = "deserialize_number_from_string")]
+    pub time: u64,
+}
+
+pub fn mark(instrument_name: &str) -> String {
+    format!("mark.{instrument_name}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::from_str;
+
+    #[test]
+    fn check_structure() {
+        let json = "{
+            \"instrument_name\": \"BTCUSD-PERP\",
+            \"subscription\": \"mark.BTCUSD-PERP\",
+            \"channel\": \"mark\",
+            \"data\": [
+              {
+                \"v\": \"50490.1\",
+                \"t\": 1613580710000
+              }
+            ]
+          }";
+        let mark_result = from_str::<MarkPriceResult>(json).unwrap();
+        assert_eq!(mark_result.instrument_name, "BTCUSD-PERP");
+        assert_eq!(mark_result.subscription, "mark.BTCUSD-PERP");
+        assert_eq!(mark_result.data.len(), 1);
+
+        // The data
+        let data = &mark_result.data[0];
+        assert_eq!(data.value, 50490.1);
+        assert_eq!(data.time, 1613580710000);
+    }
+
+    #[test]
+    fn check_channel() {
+        assert_eq!(mark("BTCUSD-PERP"), "mark.BTCUSD-PERP");
+    }
+}
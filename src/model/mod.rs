@@ -1,11 +1,30 @@
 mod candlestick;
 mod book;
+mod common;
+pub(crate) mod channel;
+mod funding;
+mod index;
+mod instrument;
+mod mark;
+mod order;
+mod otc_book;
+mod settlement;
+pub mod string_number;
 mod ticker;
 mod trade;
 mod user;
 
-pub use book::{BookResult, Book, book};
-pub use candlestick::{CandlestickResult, Candlestick, candlestick, TimeFrame};
-pub use ticker::{TickerResult, Ticker, ticker};
-pub use trade::{TradeResult, Trade, trade, Side};
-pub use user::{BalanceResult, Balance, PositionBalance, balance};
+pub use book::{BookResult, BookSnapshotResult, Book, Offer, OrderBook, book};
+pub use common::{Side, BookSide};
+pub use channel::Channel;
+pub use candlestick::{CandlestickResult, Candlestick, candlestick, TimeFrame, CandleAggregator};
+pub use funding::{FundingRateResult, EstimatedFundingRateResult, FundingRate, funding, estimated_funding};
+pub use index::{IndexResult, Index, index};
+pub use instrument::{InstrumentsResult, InstrumentInfo};
+pub use mark::{MarkPriceResult, MarkPrice, mark};
+pub use order::{OrderResult, Order, OrderListResult, OrderPage, user_order, UserTradeResult, UserTrade, user_trade};
+pub use otc_book::{OtcBookResult, OtcBook, OtcQuote, otc_book};
+pub use settlement::{SettlementResult, Settlement, settlement};
+pub use ticker::{TickerResult, Ticker, ticker, all_tickers};
+pub use trade::{TradeResult, Trade, trade};
+pub use user::{BalanceResult, Balance, AccountSummaryResult, PositionBalance, balance, PositionResult, Position, PositionBalanceResult, PositionBalanceUpdate, BalanceUpdate, positions, position_balance};
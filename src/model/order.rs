@@ -0,0 +1,312 @@
+use serde::{Serialize, Deserialize};
+use serde_aux::prelude::{deserialize_number_from_string, deserialize_option_number_from_string};
+
+use super::common::Side;
+
+// Main container of the user orders
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct OrderResult {
+    /// Just the instrument name
+    pub instrument_name: String,
+
+    /// Subscription name used to subscribe this event
+    pub subscription: String,
+
+    /// The orders that changed
+    pub data: Vec<Order>
+}
+
+/// Order received from subscription or from the order requests
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Order {
+    /// Order id
+    pub order_id: String,
+
+    /// Client order id, if provided when the order was created
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_oid: Option<String>,
+
+    /// Instrument name, for example ETH_CRO
+    pub instrument_name: String,
+
+    /// ACTIVE, CANCELED, FILLED, REJECTED or EXPIRED
+    pub status: String,
+
+    /// Side, buy or sell
+    pub side: Side,
+
+    /// LIMIT, MARKET, STOP_LOSS, STOP_LIMIT, TAKE_PROFIT or TAKE_PROFIT_LIMIT
+    #[serde(rename = "type")]
+    pub order_type: String,
+
+    /// Price specified in the order
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub price: f64,
+
+    /// Quantity specified in the order
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub quantity: f64,
+
+    /// Quantity executed so far
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub cumulative_quantity: f64,
+
+    /// Value executed so far
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub cumulative_value: f64,
+
+    /// Average price of the executions
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub avg_price: f64,
+
+    /// Currency used for the fees
+    pub fee_currency: String,
+
+    /// GOOD_TILL_CANCEL, FILL_OR_KILL or IMMEDIATE_OR_CANCEL
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub time_in_force: Option<String>,
+
+    /// Creation time
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub create_time: u64,
+
+    /// Last update time
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub update_time: u64,
+}
+
+/// Result of the `private/get-open-orders` and `private/get-order-history` requests
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct OrderListResult {
+    /// Total number of orders, only sent for open orders
+    #[serde(default, deserialize_with = "deserialize_option_number_from_string")]
+    pub count: Option<u64>,
+
+    /// The orders of the page
+    #[serde(default)]
+    pub order_list: Vec<Order>
+}
+
+/// A page of orders
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct OrderPage {
+    /// Total number of orders, only known for open orders
+    pub count: Option<u64>,
+
+    /// The page requested, starting at 0
+    pub page: u32,
+
+    /// The orders of the page
+    pub orders: Vec<Order>,
+}
+
+pub fn user_order(instrument_name: &str) -> String {
+    format!("user.order.{instrument_name}")
+}
+
+// Main container of the user trades
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct UserTradeResult {
+    /// Just the instrument name
+    pub instrument_name: String,
+
+    /// Subscription name used to subscribe this event
+    pub subscription: String,
+
+    /// The executions of the orders of the user
+    pub data: Vec<UserTrade>
+}
+
+/// Execution of an order of the user
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct UserTrade {
+    /// Trade id
+    pub trade_id: String,
+
+    /// Id of the executed order
+    pub order_id: String,
+
+    /// Instrument name, for example ETH_CRO
+    pub instrument_name: String,
+
+    /// Side of the order, buy or sell
+    pub side: Side,
+
+    /// Price of the execution
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub traded_price: f64,
+
+    /// Quantity executed
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub traded_quantity: f64,
+
+    /// Fee paid for the execution
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub fee: f64,
+
+    /// Currency used for the fee
+    pub fee_currency: String,
+
+    /// TAKER or MAKER, when sent
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub liquidity_indicator: Option<String>,
+
+    /// Execution time
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub create_time: u64,
+}
+
+pub fn user_trade(instrument_name: &str) -> String {
+    format!("user.trade.{instrument_name}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::from_str;
+
+    #[test]
+    fn check_structure() {
+        let json = "{
+            \"instrument_name\": \"ETH_CRO\",
+            \"subscription\": \"user.order.ETH_CRO\",
+            \"channel\": \"user.order\",
+            \"data\": [
+              {
+                \"status\": \"ACTIVE\",
+                \"side\": \"BUY\",
+                \"price\": 1,
+                \"quantity\": \"1.5\",
+                \"order_id\": \"366455245775097673\",
+                \"client_oid\": \"my_order_0002\",
+                \"create_time\": 1588758017375,
+                \"update_time\": 1588758017411,
+                \"type\": \"LIMIT\",
+                \"instrument_name\": \"ETH_CRO\",
+                \"cumulative_quantity\": 0,
+                \"cumulative_value\": 0,
+                \"avg_price\": 0,
+                \"fee_currency\": \"CRO\",
+                \"time_in_force\": \"GOOD_TILL_CANCEL\"
+              }
+            ]
+          }";
+        let order_result = from_str::<OrderResult>(json).unwrap();
+        assert_eq!(order_result.instrument_name, "ETH_CRO");
+        assert_eq!(order_result.subscription, "user.order.ETH_CRO");
+        assert_eq!(order_result.data.len(), 1);
+
+        // The data
+        let data = &order_result.data[0];
+        assert_eq!(data.status, "ACTIVE");
+        assert_eq!(data.side, Side::Buy);
+        assert_eq!(data.price, 1.0);
+        assert_eq!(data.quantity, 1.5);
+        assert_eq!(data.order_id, "366455245775097673");
+        assert_eq!(data.client_oid.as_deref(), Some("my_order_0002"));
+        assert_eq!(data.create_time, 1588758017375);
+        assert_eq!(data.update_time, 1588758017411);
+        assert_eq!(data.order_type, "LIMIT");
+        assert_eq!(data.cumulative_quantity, 0.0);
+        assert_eq!(data.fee_currency, "CRO");
+        assert_eq!(data.time_in_force.as_deref(), Some("GOOD_TILL_CANCEL"));
+    }
+
+    #[test]
+    fn check_order_list() {
+        let json = "{
+            \"count\": 1177,
+            \"order_list\": [
+              {
+                \"status\": \"FILLED\",
+                \"side\": \"SELL\",
+                \"price\": \"0.12\",
+                \"quantity\": \"10\",
+                \"order_id\": \"367107623521528457\",
+                \"create_time\": 1588777459755,
+                \"update_time\": 1588777460700,
+                \"type\": \"MARKET\",
+                \"instrument_name\": \"CRO_USDT\",
+                \"cumulative_quantity\": \"10\",
+                \"cumulative_value\": \"1.2\",
+                \"avg_price\": \"0.12\",
+                \"fee_currency\": \"USDT\"
+              }
+            ]
+          }";
+        let list = from_str::<OrderListResult>(json).unwrap();
+        assert_eq!(list.count, Some(1177));
+        assert_eq!(list.order_list.len(), 1);
+        assert_eq!(list.order_list[0].side, Side::Sell);
+        assert_eq!(list.order_list[0].client_oid, None);
+        assert_eq!(list.order_list[0].avg_price, 0.12);
+
+        let list = from_str::<OrderListResult>("{\"order_list\": []}").unwrap();
+        assert_eq!(list.count, None);
+        assert!(list.order_list.is_empty());
+    }
+
+    #[test]
+    fn check_user_trade_structure() {
+        let json = "{
+            \"instrument_name\": \"ETH_CRO\",
+            \"subscription\": \"user.trade.ETH_CRO\",
+            \"channel\": \"user.trade\",
+            \"data\": [
+              {
+                \"side\": \"SELL\",
+                \"instrument_name\": \"ETH_CRO\",
+                \"fee\": 0.014,
+                \"trade_id\": \"367107655537806900\",
+                \"create_time\": 1588777459755,
+                \"traded_price\": \"7.05\",
+                \"traded_quantity\": 1,
+                \"fee_currency\": \"CRO\",
+                \"order_id\": \"367107623521528450\"
+              },
+              {
+                \"side\": \"BUY\",
+                \"instrument_name\": \"ETH_CRO\",
+                \"fee\": \"0.0035\",
+                \"trade_id\": \"367107655537806901\",
+                \"create_time\": \"1588777459760\",
+                \"traded_price\": 7,
+                \"traded_quantity\": \"0.5\",
+                \"fee_currency\": \"ETH\",
+                \"order_id\": \"367107623521528451\",
+                \"liquidity_indicator\": \"MAKER\"
+              }
+            ]
+          }";
+        let trade_result = from_str::<UserTradeResult>(json).unwrap();
+        assert_eq!(trade_result.instrument_name, "ETH_CRO");
+        assert_eq!(trade_result.subscription, "user.trade.ETH_CRO");
+        assert_eq!(trade_result.data.len(), 2);
+
+        let trade = &trade_result.data[0];
+        assert_eq!(trade.side, Side::Sell);
+        assert_eq!(trade.fee, 0.014);
+        assert_eq!(trade.trade_id, "367107655537806900");
+        assert_eq!(trade.order_id, "367107623521528450");
+        assert_eq!(trade.create_time, 1588777459755);
+        assert_eq!(trade.traded_price, 7.05);
+        assert_eq!(trade.traded_quantity, 1.0);
+        assert_eq!(trade.fee_currency, "CRO");
+        assert_eq!(trade.liquidity_indicator, None);
+
+        let trade = &trade_result.data[1];
+        assert_eq!(trade.side, Side::Buy);
+        assert_eq!(trade.fee, 0.0035);
+        assert_eq!(trade.create_time, 1588777459760);
+        assert_eq!(trade.traded_quantity, 0.5);
+        assert_eq!(trade.liquidity_indicator.as_deref(), Some("MAKER"));
+
+        assert!(from_str::<UserTradeResult>(&json.replace("\"SELL\"", "\"sell\"")).is_err());
+    }
+
+    #[test]
+    fn check_channel() {
+        assert_eq!(user_order("ETH_CRO"), "user.order.ETH_CRO");
+        assert_eq!(user_trade("ETH_CRO"), "user.trade.ETH_CRO");
+    }
+}
@@ -0,0 +1,201 @@
+use serde::{
+    de::{self, SeqAccess, Visitor},
+    ser::SerializeTuple,
+    Deserialize, Deserializer, Serialize, Serializer,
+};
+use serde_aux::prelude::deserialize_number_from_string;
+use std::fmt::Display;
+use std::str::FromStr;
+
+use super::book::AsString;
+use super::string_number;
+
+// Main container of an OTC book
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct OtcBookResult {
+    /// Just the instrument name
+    pub instrument_name: String,
+
+    /// Subscription name used to subscribe this event
+    pub subscription: String,
+
+    /// The actual OTC book data
+    pub data: Vec<OtcBook>,
+}
+
+/// A level of the OTC book
+#[derive(Debug, Clone, PartialEq)]
+pub struct OtcQuote {
+    /// price
+    pub price: f64,
+
+    /// Quantity
+    pub quantity: f64,
+
+    /// Number of quotes
+    pub quotes: u64,
+}
+
+/// Convert the struct into the tuple format, with the numbers as strings like the exchange
+impl Serialize for OtcQuote {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut tup = serializer.serialize_tuple(3)?;
+        tup.serialize_element(&AsString(&self.price, None))?;
+        tup.serialize_element(&AsString(&self.quantity, None))?;
+        tup.serialize_element(&AsString(&self.quotes, None))?;
+        tup.end()
+    }
+}
+
+/// The elements are strings or plain numbers, the strings are parsed in place
+#[derive(Deserialize)]
+#[serde(transparent)]
+#[serde(bound(deserialize = "T: FromStr + Deserialize<'de>, <T as FromStr>::Err: Display"))]
+struct Element<T>(#[serde(deserialize_with = "string_number::deserialize")] T);
+
+struct OtcQuoteVisitor;
+/// Convert the tuple into a struct
+impl<'de> Visitor<'de> for OtcQuoteVisitor {
+    type Value = OtcQuote;
+
+    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            formatter,
+            "a sequence of numbers as strings (price, quantity, quotes)"
+        )
+    }
+
+    fn visit_seq<M>(self, mut seq: M) -> Result<Self::Value, M::Error>
+    where
+        M: SeqAccess<'de>,
+    {
+        let Element(price) = seq
+            .next_element()?
+            .ok_or_else(|| de::Error::custom("Missing price"))?;
+        let Element(quantity) = seq
+            .next_element()?
+            .ok_or_else(|| de::Error::custom("Missing quantity"))?;
+        let Element(quotes) = seq
+            .next_element()?
+            .ok_or_else(|| de::Error::custom("Missing quotes"))?;
+
+        // The OTC levels may carry more elements, they are not needed
+        while seq.next_element::<de::IgnoredAny>()?.is_some() {}
+
+        Ok(OtcQuote {
+            price,
+            quantity,
+            quotes,
+        })
+    }
+}
+
+impl<'de> Deserialize<'de> for OtcQuote {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_seq(OtcQuoteVisitor)
+    }
+}
+
+/// OTC book received from subscription.
+///
+/// The quotes of the OTC desk are firm only until `expiry_time`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct OtcBook {
+    /// The value is: (price, quantity, number of quotes)
+    pub bids: Vec<OtcQuote>,
+
+    /// The value is: (price, quantity, number of quotes)
+    pub asks: Vec<OtcQuote>,
+
+    /// Publish time
+    #[serde(rename = "t", deserialize_with = "deserialize_number_from_string")]
+    pub time: u64,
+
+    /// Time when the quotes expire
+    #[serde(rename = "tt", deserialize_with = "deserialize_number_from_string")]
+    pub expiry_time: u64,
+}
+
+impl OtcBook {
+    /// True if the quotes are no longer valid at `now` (millis since epoch)
+    pub fn is_expired(&self, now: u64) -> bool {
+        now >= self.expiry_time
+    }
+}
+
+pub fn otc_book(instrument_name: &str) -> String {
+    format!("otc_book.{instrument_name}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::from_str;
+
+    #[test]
+    fn check_structure() {
+        let json = "{
+            \"instrument_name\": \"BTC_USDT\",
+            \"subscription\": \"otc_book.BTC_USDT\",
+            \"channel\": \"otc_book\",
+            \"data\": [
+              {
+                \"asks\": [
+                  [\"50126.000000\", \"0.400000\", \"2\"],
+                  [\"50130.000000\", \"1.279000\", \"1\"]
+                ],
+                \"bids\": [],
+                \"t\": 1613580710768,
+                \"tt\": 1613580715768
+              }
+            ]
+          }";
+        let otc_book_result = from_str::<OtcBookResult>(json).unwrap();
+        assert_eq!(otc_book_result.instrument_name, "BTC_USDT");
+        assert_eq!(otc_book_result.subscription, "otc_book.BTC_USDT");
+        assert_eq!(otc_book_result.data.len(), 1);
+
+        // The data
+        let data = &otc_book_result.data[0];
+        assert!(data.bids.is_empty());
+        assert_eq!(data.asks.len(), 2);
+        assert_eq!(
+            data.asks[1],
+            OtcQuote {
+                price: 50130.0,
+                quantity: 1.279,
+                quotes: 1,
+            }
+        );
+        assert_eq!(data.time, 1613580710768);
+        assert_eq!(data.expiry_time, 1613580715768);
+        assert!(!data.is_expired(1613580710768));
+        assert!(data.is_expired(1613580715768));
+    }
+
+    #[test]
+    fn check_extra_quote_elements() {
+        let quote = from_str::<OtcQuote>("[\"50126.5\", \"0.4\", \"2\", \"1613580715768\"]").unwrap();
+        assert_eq!(
+            quote,
+            OtcQuote {
+                price: 50126.5,
+                quantity: 0.4,
+                quotes: 2,
+            }
+        );
+        assert_eq!(serde_json::to_string(&quote).unwrap(), "[\"50126.5\",\"0.4\",\"2\"]");
+        assert!(from_str::<OtcQuote>("[\"50126.5\", \"0.4\", \"2.5\"]").is_err());
+    }
+
+    #[test]
+    fn check_channel() {
+        assert_eq!(otc_book("BTC_USDT"), "otc_book.BTC_USDT");
+    }
+}
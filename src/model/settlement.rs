@@ -0,0 +1,85 @@
+use serde::{Serialize, Deserialize};
+use serde_aux::prelude::deserialize_number_from_string;
+
+// Main container of a settlement price
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SettlementResult {
+    /// Just the instrument name, for example BTCUSD-210528m2
+    pub instrument_name: String,
+
+    /// Subscription name used to subscribe this event
+    pub subscription: String,
+
+    /// The actual settlement data, settlements are rare so it is usually empty
+    pub data: Vec<Settlement>
+}
+
+/// Settlement element received from subscription
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Settlement {
+    /// Instrument settled, only present in some payloads
+    #[serde(rename = "i", default, skip_serializing_if = "Option::is_none")]
+    pub instrument_name: Option<String>,
+
+    /// Settlement price
+    #[serde(rename = "v", deserialize_with = "deserialize_number_from_string")]
+    pub value: f64,
+
+    /// Settlement time
+    #[serde(rename = "t", deserialize_with = "deserialize_number_from_string")]
+    pub time: u64,
+}
+
+pub fn settlement(instrument_name: &str) -> String {
+    format!("settlement.{instrument_name}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::from_str;
+
+    #[test]
+    fn check_structure() {
+        let json = "{
+            \"instrument_name\": \"BTCUSD-210528m2\",
+            \"subscription\": \"settlement.BTCUSD-210528m2\",
+            \"channel\": \"settlement\",
+            \"data\": [
+              {
+                \"i\": \"BTCUSD-210528m2\",
+                \"v\": \"50000\",
+                \"t\": 1622188800000
+              }
+            ]
+          }";
+        let settlement_result = from_str::<SettlementResult>(json).unwrap();
+        assert_eq!(settlement_result.instrument_name, "BTCUSD-210528m2");
+        assert_eq!(settlement_result.subscription, "settlement.BTCUSD-210528m2");
+        assert_eq!(settlement_result.data.len(), 1);
+
+        // The data
+        let data = &settlement_result.data[0];
+        assert_eq!(data.instrument_name.as_deref(), Some("BTCUSD-210528m2"));
+        assert_eq!(data.value, 50000.0);
+        assert_eq!(data.time, 1622188800000);
+    }
+
+    #[test]
+    fn check_empty_data() {
+        let json = "{
+            \"instrument_name\": \"BTCUSD-210528m2\",
+            \"subscription\": \"settlement.BTCUSD-210528m2\",
+            \"channel\": \"settlement\",
+            \"data\": []
+          }";
+        let settlement_result = from_str::<SettlementResult>(json).unwrap();
+        assert_eq!(settlement_result.instrument_name, "BTCUSD-210528m2");
+        assert!(settlement_result.data.is_empty());
+    }
+
+    #[test]
+    fn check_channel() {
+        assert_eq!(settlement("BTCUSD-210528m2"), "settlement.BTCUSD-210528m2");
+    }
+}
@@ -0,0 +1,257 @@
+//! Serde helpers for the numbers that the exchange sends as strings.
+//!
+//! Use it with `#[serde(with = "string_number")]` so a parsed payload is
+//! serialized back with its numbers as strings, like the exchange sends them.
+//! The values round trip, not the text: the trailing zeros of the exchange are
+//! dropped, `"50126.000000"` is written back as `"50126"`. `Offer` and
+//! `Candlestick` also keep the text of such numbers so they are written back
+//! exactly as received.
+
+use serde::de::{self, IntoDeserializer, Visitor};
+use serde::{Deserialize, Deserializer, Serializer};
+use std::fmt::{self, Display, Write};
+use std::marker::PhantomData;
+use std::str::FromStr;
+
+/// Serialize a number as a string, using the shortest representation that
+/// parses back to the same value
+pub fn serialize<T, S>(value: &T, serializer: S) -> Result<S::Ok, S::Error>
+where
+    T: Display,
+    S: Serializer,
+{
+    serializer.collect_str(value)
+}
+
+/// Deserialize a number sent either as a string or as a number.
+///
+/// Strings are parsed where they are, borrowed from the input, so no `String`
+/// is allocated for them.
+pub fn deserialize<'de, T, D>(deserializer: D) -> Result<T, D::Error>
+where
+    T: FromStr + Deserialize<'de>,
+    <T as FromStr>::Err: Display,
+    D: Deserializer<'de>,
+{
+    deserializer.deserialize_any(NumberVisitor(PhantomData))
+}
+
+/// The text of a number when it is not how `value` is written, like the trailing
+/// zeros of `"50126.000000"`. Nothing is allocated when the text is the usual one,
+/// and most texts are recognized as such without writing `value`
+pub(crate) fn original_text(value: f64, text: &str) -> Option<String> {
+    /// Consumes `text` while it matches what is written
+    struct Matches<'a>(&'a str);
+
+    impl Write for Matches<'_> {
+        fn write_str(&mut self, s: &str) -> fmt::Result {
+            self.0 = self.0.strip_prefix(s).ok_or(fmt::Error)?;
+            Ok(())
+        }
+    }
+
+    match is_written_as(text) {
+        Some(true) => None,
+        Some(false) => Some(text.to_owned()),
+        None => {
+            let mut rest = Matches(text);
+            match write!(rest, "{value}") {
+                Ok(()) if rest.0.is_empty() => None,
+                _ => Some(text.to_owned()),
+            }
+        }
+    }
+}
+
+/// Whether `text` is how its value is written, when that shows without writing it.
+/// The value is written without leading or trailing zeros, and two texts of at most
+/// 15 digits never parse to the same `f64`, so such a text is the shortest for its value
+fn is_written_as(text: &str) -> Option<bool> {
+    let unsigned = text.strip_prefix('-').unwrap_or(text);
+    let (integer, fraction) = match unsigned.split_once('.') {
+        Some((integer, fraction)) => (integer, Some(fraction)),
+        None => (unsigned, None),
+    };
+    let digits = |part: &str| !part.is_empty() && part.bytes().all(|byte| byte.is_ascii_digit());
+    if !digits(integer) || !fraction.is_none_or(digits) {
+        return None;
+    }
+    if (integer.len() > 1 && integer.starts_with('0'))
+        || fraction.is_some_and(|fraction| fraction.ends_with('0'))
+    {
+        return Some(false);
+    }
+    (integer.len() + fraction.map_or(0, str::len) <= 15).then_some(true)
+}
+
+/// Serialize `text` as is if there is one, `value` as a string otherwise
+pub(crate) fn serialize_with_text<T, S>(
+    value: &T,
+    text: Option<&str>,
+    serializer: S,
+) -> Result<S::Ok, S::Error>
+where
+    T: Display,
+    S: Serializer,
+{
+    match text {
+        Some(text) => serializer.serialize_str(text),
+        None => serialize(value, serializer),
+    }
+}
+
+/// A number and its `original_text`, numbers sent as numbers have none
+pub(crate) struct WithText {
+    pub(crate) value: f64,
+    pub(crate) text: Option<String>,
+}
+
+impl<'de> Deserialize<'de> for WithText {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(WithTextVisitor)
+    }
+}
+
+struct WithTextVisitor;
+
+impl<'de> Visitor<'de> for WithTextVisitor {
+    type Value = WithText;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        NumberVisitor::<f64>(PhantomData).expecting(formatter)
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<Self::Value, E> {
+        let value = v.parse().map_err(de::Error::custom)?;
+        let text = original_text(value, v);
+        Ok(WithText { value, text })
+    }
+
+    fn visit_u64<E: de::Error>(self, v: u64) -> Result<Self::Value, E> {
+        let value = NumberVisitor(PhantomData).visit_u64(v)?;
+        Ok(WithText { value, text: None })
+    }
+
+    fn visit_i64<E: de::Error>(self, v: i64) -> Result<Self::Value, E> {
+        let value = NumberVisitor(PhantomData).visit_i64(v)?;
+        Ok(WithText { value, text: None })
+    }
+
+    fn visit_f64<E: de::Error>(self, v: f64) -> Result<Self::Value, E> {
+        let value = NumberVisitor(PhantomData).visit_f64(v)?;
+        Ok(WithText { value, text: None })
+    }
+}
+
+struct NumberVisitor<T>(PhantomData<T>);
+
+impl<'de, T> Visitor<'de> for NumberVisitor<T>
+where
+    T: FromStr + Deserialize<'de>,
+    <T as FromStr>::Err: Display,
+{
+    type Value = T;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        write!(formatter, "a number or a string with a number")
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<T, E> {
+        v.parse().map_err(de::Error::custom)
+    }
+
+    fn visit_u64<E: de::Error>(self, v: u64) -> Result<T, E> {
+        T::deserialize(v.into_deserializer())
+    }
+
+    fn visit_i64<E: de::Error>(self, v: i64) -> Result<T, E> {
+        T::deserialize(v.into_deserializer())
+    }
+
+    fn visit_f64<E: de::Error>(self, v: f64) -> Result<T, E> {
+        T::deserialize(v.into_deserializer())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::Deserialize;
+
+    #[derive(Deserialize, Debug, PartialEq)]
+    struct Numbers {
+        #[serde(with = "super")]
+        price: f64,
+        #[serde(with = "super")]
+        count: u64,
+    }
+
+    #[test]
+    fn strings_and_numbers() {
+        let numbers =
+            serde_json::from_str::<Numbers>("{\"price\": \"1.5\", \"count\": \"3\"}").unwrap();
+        assert_eq!(
+            numbers,
+            Numbers {
+                price: 1.5,
+                count: 3
+            }
+        );
+
+        let numbers = serde_json::from_str::<Numbers>("{\"price\": 2, \"count\": 4}").unwrap();
+        assert_eq!(
+            numbers,
+            Numbers {
+                price: 2.0,
+                count: 4
+            }
+        );
+
+        assert!(serde_json::from_str::<Numbers>("{\"price\": \"abc\", \"count\": 4}").is_err());
+        assert!(serde_json::from_str::<Numbers>("{\"price\": 1, \"count\": -4}").is_err());
+    }
+
+    #[test]
+    fn original_text_agrees_with_display() {
+        for i in 0..5000 {
+            let value = (i as f64 - 2500.0) * 0.0137;
+            for precision in 0..18 {
+                let text = format!("{value:.precision$}");
+                let parsed: f64 = text.parse().unwrap();
+                let different = parsed.to_string() != text;
+                assert_eq!(
+                    super::original_text(parsed, &text).is_some(),
+                    different,
+                    "{text}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn original_text_only_when_different() {
+        assert_eq!(super::original_text(50126.5, "50126.5"), None);
+        assert_eq!(super::original_text(0.00001234, "0.00001234"), None);
+        assert_eq!(super::original_text(2.0, "2"), None);
+        assert_eq!(super::original_text(-0.5, "-0.5"), None);
+        assert_eq!(super::original_text(0.0, "0"), None);
+        assert_eq!(super::original_text(0.1 + 0.2, "0.30000000000000004"), None);
+        assert_eq!(super::original_text(1e20, "100000000000000000000"), None);
+        assert_eq!(
+            super::original_text(50126.0, "50126.000000").as_deref(),
+            Some("50126.000000")
+        );
+        assert_eq!(
+            super::original_text(0.4, "0.4000").as_deref(),
+            Some("0.4000")
+        );
+        assert_eq!(super::original_text(1e-7, "1E-7").as_deref(), Some("1E-7"));
+        assert_eq!(super::original_text(5.0, "5.").as_deref(), Some("5."));
+        assert_eq!(super::original_text(5.0, "05").as_deref(), Some("05"));
+        assert_eq!(super::original_text(0.5, ".5").as_deref(), Some(".5"));
+        assert_eq!(super::original_text(5.0, "+5").as_deref(), Some("+5"));
+        assert_eq!(
+            super::original_text(0.1, "0.1000000000000000055511151231257827").as_deref(),
+            Some("0.1000000000000000055511151231257827")
+        );
+    }
+}
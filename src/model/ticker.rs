@@ -2,9 +2,10 @@ use serde::{Serialize, Deserialize};
 use serde_aux::prelude::deserialize_number_from_string;
 
 // Main container of a ticker
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct TickerResult {
-    /// Just the instrument name
+    /// Just the instrument name, empty for the `ticker` channel of all the instruments
+    #[serde(default)]
     pub instrument_name: String,
 
     /// Subscription name used to subscribe this event
@@ -14,13 +15,36 @@ pub struct TickerResult {
     pub data: Vec<Ticker>
 }
 
+impl TickerResult {
+    /// True for the results of the `ticker` channel, with the tickers of several instruments
+    pub fn is_all_instruments(&self) -> bool {
+        self.subscription == all_tickers()
+    }
+
+    /// The tickers with their instrument, taken from the ticker itself when it has one
+    pub fn tickers(&self) -> impl Iterator<Item = (&str, &Ticker)> {
+        self.data.iter().map(|ticker| {
+            (ticker.instrument_name.as_deref().unwrap_or(&self.instrument_name), ticker)
+        })
+    }
+}
+
 pub fn ticker(instrument_name: &str) -> String {
   format!("ticker.{instrument_name}")
 }
 
+/// The channel of the tickers of every instrument
+pub fn all_tickers() -> String {
+  "ticker".to_owned()
+}
+
 /// Ticker element received from subscription
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct Ticker {
+    /// The instrument name, only in the `ticker` channel of all the instruments
+    #[serde(rename = "i", default, skip_serializing_if = "Option::is_none")]
+    pub instrument_name: Option<String>,
+
     /// Price of the 24h highest trade
     #[serde(rename = "h")]
     pub highest: f32,
@@ -55,6 +79,7 @@ pub struct Ticker {
 }
 
 #[cfg(test)]
+#[allow(clippy::excessive_precision)]
 mod tests {
     use super::*;
     use serde_json::from_str;
@@ -93,6 +118,31 @@ mod tests {
         assert_eq!(data.best, 1.12345680);
         assert_eq!(data.change, -0.44564773);
         assert_eq!(data.time, 1587523078844);
-        
+        assert_eq!(data.instrument_name, None);
+        assert!(!ticker_result.is_all_instruments());
+        assert_eq!(ticker_result.tickers().next().unwrap().0, "ETH_CRO");
+    }
+
+    #[test]
+    fn check_all_instruments() {
+        let json = "{
+              \"subscription\": \"ticker\",
+              \"channel\": \"ticker\",
+              \"data\": [
+                {\"i\": \"ETH_CRO\", \"h\": 1, \"v\": 10, \"a\": 0.5, \"l\": 0.01, \"b\": 0.02, \"k\": 0.6, \"c\": -0.4, \"t\": 1587523078844},
+                {\"i\": \"BTC_USDT\", \"h\": 70000, \"v\": 12.5, \"a\": 68000, \"l\": 65000, \"b\": 67999, \"k\": 68001, \"c\": 0.02, \"t\": \"1587523078845\"}
+              ]
+            }";
+        let ticker_result = from_str::<TickerResult>(json).unwrap();
+        assert!(ticker_result.is_all_instruments());
+        assert_eq!(ticker_result.instrument_name, "");
+        let instruments: Vec<&str> = ticker_result.tickers().map(|(instrument_name, _)| instrument_name).collect();
+        assert_eq!(instruments, vec!["ETH_CRO", "BTC_USDT"]);
+        assert_eq!(ticker_result.data[1].latest, 68000.0);
+        assert_eq!(ticker_result.data[1].time, 1587523078845);
+
+        // The instrument of the tickers is written back
+        let text = serde_json::to_string(&ticker_result).unwrap();
+        assert_eq!(from_str::<TickerResult>(&text).unwrap(), ticker_result);
     }
 }
\ No newline at end of file
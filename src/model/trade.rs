@@ -1,8 +1,10 @@
 use serde::{Serialize, Deserialize};
 use serde_aux::prelude::deserialize_number_from_string;
 
+use super::common::Side;
+
 // Main container of a trade
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct TradeResult {
     /// Just the instrument name
     pub instrument_name: String,
@@ -15,7 +17,7 @@ pub struct TradeResult {
 }
 
 /// Trade element received from subscription
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct Trade {
     /// Price
     #[serde(rename = "p", deserialize_with = "deserialize_number_from_string")]
@@ -25,7 +27,7 @@ pub struct Trade {
     #[serde(rename = "q", deserialize_with = "deserialize_number_from_string")]
     pub quantity: f64,
 
-    /// Side, buy or sell (exactly these strings)
+    /// Side of the taker, buy or sell (exactly these strings)
     #[serde(rename = "s")]
     pub side: Side,
 
@@ -38,20 +40,10 @@ pub struct Trade {
     pub time: u64,
 }
 
-#[derive(Serialize,Deserialize, Debug, PartialEq)]
-pub enum Side {
-  #[serde(rename = "BUY")]
-  Buy,
-  #[serde(rename = "SELL")]
-  Sell
-}
-
-impl std::fmt::Display for Side {
-    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        match *self {
-          Side::Buy => write!(f, "BUY"),
-          Side::Sell => write!(f, "SELL"),
-        }
+impl Trade {
+    /// The side of a trade is the one of the taker, so the buyer is the maker of the sells
+    pub fn is_buyer_maker(&self) -> bool {
+        self.side == Side::Sell
     }
 }
 
@@ -99,6 +91,7 @@ mod tests {
         assert_eq!(data.price, 162.12);
         assert_eq!(data.quantity, 11.085);
         assert_eq!(data.side, Side::Buy);
+        assert!(!data.is_buyer_maker());
         assert_eq!(data.id, 1210447366);
         assert_eq!(data.time, 1587523078844);
 
@@ -106,6 +99,7 @@ mod tests {
         assert_eq!(data2.price, 1162.12);
         assert_eq!(data2.quantity, 111.085);
         assert_eq!(data2.side, Side::Sell);
+        assert!(data2.is_buyer_maker());
         assert_eq!(data2.id, 11210447366);
         assert_eq!(data2.time, 11587523078844);
         
@@ -3,7 +3,7 @@ use serde_aux::prelude::deserialize_number_from_string;
 
 
 // Main container of the user balance
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct BalanceResult {
     /// Subscription name used to subscribe this event
     pub subscription: String,
@@ -12,28 +12,41 @@ pub struct BalanceResult {
     pub data: Vec<Balance>
 }
 
-/// Balance element received from subscription
-#[derive(Serialize, Deserialize, Debug)]
+/// Balance element received from subscription or from `private/get-account-summary`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct Balance {
     /// Currency name
     pub currency: String,
 
     /// Total balance
-    pub balance: f32,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub balance: f64,
 
     /// Total available
-    pub available: f32,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub available: f64,
 
     /// Total in any order
-    pub order: f32,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub order: f64,
 
     /// Total staked
-    pub stake: f32
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub stake: f64
+
+}
 
+/// Result of a `private/get-account-summary` request
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct AccountSummaryResult {
+    /// Balance of every requested currency
+    #[serde(default)]
+    pub accounts: Vec<Balance>
 }
 
 
-#[derive(Serialize, Deserialize, Debug)]
+#[allow(dead_code)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct BalanceResult2 {
     /// Subscription name used to subscribe this event
     pub subscription: String,
@@ -43,7 +56,8 @@ pub struct BalanceResult2 {
 }
 
 /// Balance element received from subscription
-#[derive(Serialize, Deserialize, Debug)]
+#[allow(dead_code)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct Balance2 {
     /// Balance that user can open new order (Margin Balance - Initial Margin)
     #[serde(deserialize_with = "deserialize_number_from_string")]
@@ -105,7 +119,7 @@ pub struct Balance2 {
 }
 
 /// Position balance
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct PositionBalance {
 
     /// Instrument name of the collateral e.g. USD, CRO, USDT, or DAI
@@ -134,4 +148,248 @@ pub struct PositionBalance {
  
 pub fn balance() -> String {
     ("user.balance").to_string()
-  }
\ No newline at end of file
+  }
+
+// Main container of the user positions
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct PositionResult {
+    /// Subscription name used to subscribe this event
+    pub subscription: String,
+
+    /// The positions that changed
+    pub data: Vec<Position>
+}
+
+/// Position element received from subscription
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Position {
+    /// Account id
+    pub account_id: String,
+
+    /// Instrument name, for example BTCUSD-PERP
+    pub instrument_name: String,
+
+    /// Instrument type, for example PERPETUAL_SWAP
+    #[serde(rename = "type")]
+    pub instrument_type: String,
+
+    /// Position quantity, negative when the position is short
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub quantity: f64,
+
+    /// Position cost or value in USD
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub cost: f64,
+
+    /// Profit and loss for the open position
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub open_position_pnl: f64,
+
+    /// Open position cost
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub open_pos_cost: f64,
+
+    /// Profit and loss in the current trading session
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub session_pnl: f64,
+
+    /// Updated time
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub update_timestamp_ms: u64,
+}
+
+impl Position {
+    pub fn is_long(&self) -> bool {
+        self.quantity > 0.0
+    }
+
+    pub fn is_short(&self) -> bool {
+        self.quantity < 0.0
+    }
+}
+
+// Main container of the position and balance updates
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct PositionBalanceResult {
+    /// Subscription name used to subscribe this event
+    pub subscription: String,
+
+    /// The actual updates
+    pub data: Vec<PositionBalanceUpdate>
+}
+
+/// Balances and positions changed by the same event
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct PositionBalanceUpdate {
+    /// Balances that changed
+    pub balances: Vec<BalanceUpdate>,
+
+    /// Positions that changed
+    pub positions: Vec<Position>
+}
+
+/// Balance of a collateral after an update
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct BalanceUpdate {
+    /// Instrument name of the collateral e.g. USD, CRO, USDT, or DAI
+    pub instrument_name: String,
+
+    /// Quantity of the collateral
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub quantity: f64,
+
+    /// Updated time
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub update_timestamp_ms: u64,
+}
+
+pub fn positions() -> String {
+    ("user.positions").to_string()
+}
+
+pub fn position_balance() -> String {
+    ("user.position_balance").to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::from_str;
+
+    #[test]
+    fn check_positions_structure() {
+        let json = "{
+            \"subscription\": \"user.positions\",
+            \"channel\": \"user.positions\",
+            \"data\": [
+              {
+                \"account_id\": \"858dbc8b-22fd-49fa-bff4-d342d98a8acb\",
+                \"quantity\": \"0.1\",
+                \"cost\": \"5037.5\",
+                \"open_position_pnl\": \"12.3\",
+                \"open_pos_cost\": \"5037.5\",
+                \"session_pnl\": \"-1.25\",
+                \"update_timestamp_ms\": 1613640000000,
+                \"instrument_name\": \"BTCUSD-PERP\",
+                \"type\": \"PERPETUAL_SWAP\"
+              }
+            ]
+          }";
+        let position_result = from_str::<PositionResult>(json).unwrap();
+        assert_eq!(position_result.subscription, "user.positions");
+        assert_eq!(position_result.data.len(), 1);
+
+        // The data
+        let data = &position_result.data[0];
+        assert_eq!(data.account_id, "858dbc8b-22fd-49fa-bff4-d342d98a8acb");
+        assert_eq!(data.instrument_name, "BTCUSD-PERP");
+        assert_eq!(data.instrument_type, "PERPETUAL_SWAP");
+        assert_eq!(data.quantity, 0.1);
+        assert_eq!(data.cost, 5037.5);
+        assert_eq!(data.open_position_pnl, 12.3);
+        assert_eq!(data.open_pos_cost, 5037.5);
+        assert_eq!(data.session_pnl, -1.25);
+        assert_eq!(data.update_timestamp_ms, 1613640000000);
+        assert!(data.is_long());
+    }
+
+    #[test]
+    fn check_position_flip() {
+        let json = "{
+            \"subscription\": \"user.position_balance\",
+            \"channel\": \"user.position_balance\",
+            \"data\": [
+              {
+                \"balances\": [
+                  {
+                    \"instrument_name\": \"USD\",
+                    \"quantity\": \"9950.25\",
+                    \"update_timestamp_ms\": 1613640000000
+                  }
+                ],
+                \"positions\": [
+                  {
+                    \"account_id\": \"858dbc8b-22fd-49fa-bff4-d342d98a8acb\",
+                    \"quantity\": \"0.1\",
+                    \"cost\": \"5037.5\",
+                    \"open_position_pnl\": \"0\",
+                    \"open_pos_cost\": \"5037.5\",
+                    \"session_pnl\": \"0\",
+                    \"update_timestamp_ms\": 1613640000000,
+                    \"instrument_name\": \"BTCUSD-PERP\",
+                    \"type\": \"PERPETUAL_SWAP\"
+                  }
+                ]
+              },
+              {
+                \"balances\": [],
+                \"positions\": [
+                  {
+                    \"account_id\": \"858dbc8b-22fd-49fa-bff4-d342d98a8acb\",
+                    \"quantity\": \"-0.2\",
+                    \"cost\": \"-10070\",
+                    \"open_position_pnl\": \"-3.5\",
+                    \"open_pos_cost\": \"-10070\",
+                    \"session_pnl\": \"4.75\",
+                    \"update_timestamp_ms\": 1613640060000,
+                    \"instrument_name\": \"BTCUSD-PERP\",
+                    \"type\": \"PERPETUAL_SWAP\"
+                  }
+                ]
+              }
+            ]
+          }";
+        let result = from_str::<PositionBalanceResult>(json).unwrap();
+        assert_eq!(result.subscription, "user.position_balance");
+        assert_eq!(result.data.len(), 2);
+
+        let first = &result.data[0];
+        assert_eq!(first.balances[0].instrument_name, "USD");
+        assert_eq!(first.balances[0].quantity, 9950.25);
+        assert!(first.positions[0].is_long());
+
+        // The position flipped from long to short
+        let second = &result.data[1];
+        assert!(second.balances.is_empty());
+        let position = &second.positions[0];
+        assert!(position.is_short());
+        assert!(!position.is_long());
+        assert_eq!(position.quantity, -0.2);
+        assert_eq!(position.cost, -10070.0);
+        assert_eq!(position.open_position_pnl, -3.5);
+        assert_eq!(position.session_pnl, 4.75);
+    }
+
+    #[test]
+    fn check_account_summary_structure() {
+        let json = "{
+            \"accounts\": [
+              {
+                \"balance\": 99999999.905000000000000000,
+                \"available\": \"99999996.905000000000000000\",
+                \"order\": 3.000000000000000000,
+                \"stake\": 0,
+                \"currency\": \"CRO\"
+              }
+            ]
+          }";
+        let summary = from_str::<AccountSummaryResult>(json).unwrap();
+        assert_eq!(summary.accounts.len(), 1);
+
+        let balance = &summary.accounts[0];
+        assert_eq!(balance.currency, "CRO");
+        assert_eq!(balance.balance, 99999999.905);
+        assert_eq!(balance.available, 99999996.905);
+        assert_eq!(balance.order, 3.0);
+        assert_eq!(balance.stake, 0.0);
+
+        let summary = from_str::<AccountSummaryResult>("{}").unwrap();
+        assert!(summary.accounts.is_empty());
+    }
+
+    #[test]
+    fn check_channels() {
+        assert_eq!(positions(), "user.positions");
+        assert_eq!(position_balance(), "user.position_balance");
+    }
+}
@@ -0,0 +1,852 @@
+use futures::{SinkExt, StreamExt};
+use log::{debug, error, info};
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime};
+use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender, WeakUnboundedSender};
+use tokio::task::JoinHandle;
+use tokio_tungstenite::tungstenite;
+use tokio_tungstenite::tungstenite::protocol::frame::coding::CloseCode;
+use tokio_tungstenite::tungstenite::protocol::{CloseFrame, Message};
+use tokio_util::sync::CancellationToken;
+
+use crate::builder::{BackoffPolicy, ClientConfig, Credentials};
+use crate::client::{
+    auth_request, nonce, open, CryptoError, EventType, HeartbeatMode, Settled, Shared, Stream,
+};
+use crate::health::ConnectionState;
+use crate::message::{Envelope, Response};
+use crate::subscription;
+use crate::{message, SubscribeResult};
+
+/// Codes of the exchange meaning the session is not authenticated:
+/// UNAUTHORIZED and AUTHENTICATION_FAILURE
+pub(crate) const UNAUTHORIZED_CODES: [u64; 2] = [10002, 40101];
+
+/// The moment a frame was read from the socket, and its text with the raw passthrough
+pub(crate) struct Received {
+    at: SystemTime,
+    instant: Instant,
+    raw: Option<Arc<str>>,
+}
+
+impl Received {
+    pub(crate) fn now() -> Received {
+        Received {
+            at: SystemTime::now(),
+            instant: Instant::now(),
+            raw: None,
+        }
+    }
+
+    pub(crate) fn envelope(&self, result: Result<SubscribeResult, CryptoError>) -> Envelope {
+        Envelope {
+            received_at: self.at,
+            received_instant: self.instant,
+            result,
+            raw: self.raw.clone(),
+        }
+    }
+}
+
+/// The text of a frame for `Envelope::raw`, the only copy made of it
+fn raw_text(text: &str) -> Arc<str> {
+    #[cfg(test)]
+    RAW_COPIES.with(|copies| copies.set(copies.get() + 1));
+    Arc::from(text)
+}
+
+#[cfg(test)]
+thread_local! {
+    /// Number of frame texts copied by `raw_text` on this thread
+    pub(crate) static RAW_COPIES: std::cell::Cell<usize> = const { std::cell::Cell::new(0) };
+}
+
+/// Aborts the task when dropped, so it does not outlive the reader
+struct AbortOnDrop(JoinHandle<()>);
+
+impl Drop for AbortOnDrop {
+    fn drop(&mut self) {
+        self.0.abort();
+    }
+}
+
+/// Task reading the frames of a connection and reconnecting it if configured
+pub(crate) struct Reader<Fut, T> {
+    pub(crate) events: EventType<T, Fut>,
+    pub(crate) container: T,
+    /// Results waiting for the dispatcher
+    pub(crate) deliveries: UnboundedSender<Envelope>,
+    pub(crate) shared: Arc<Shared>,
+    pub(crate) config: ClientConfig,
+    pub(crate) uri: String,
+    /// The subscriptions are restored once the new connection is authenticated
+    pub(crate) resubscribe_on_auth: bool,
+    /// Waiting for the response of the auth request sent when the session expired
+    pub(crate) reauthenticating: bool,
+    /// Auth requests sent since the session last worked
+    pub(crate) reauth_attempts: u32,
+    /// The exchange announced a maintenance, reconnect with the maintenance backoff
+    pub(crate) maintenance: bool,
+}
+
+impl<Fut: std::future::Future<Output = ()> + Send + Sync + 'static, T: Clone + Send + 'static>
+    Reader<Fut, T>
+{
+    pub(crate) async fn run(
+        mut self,
+        mut read: Stream,
+        queue: UnboundedReceiver<Envelope>,
+    ) -> Result<(), CryptoError> {
+        let mut dispatcher = AbortOnDrop(spawn_dispatcher(
+            Arc::clone(&self.events),
+            self.container.clone(),
+            Arc::clone(&self.shared),
+            queue,
+        ));
+        let token = self.config.cancellation_token.clone();
+        let _ping = self
+            .config
+            .ping_interval
+            .map(|interval| AbortOnDrop(spawn_ping(self.shared.clone(), interval, token.clone())));
+
+        info!("Listener ready");
+        let result = loop {
+            let result = self.read(&mut read).await;
+            let Some(backoff) = self.config.backoff.clone() else {
+                break result;
+            };
+            if self.shared.closing.load(Ordering::SeqCst) {
+                break result;
+            }
+            let backoff = match self.maintenance {
+                true => self.config.maintenance_backoff.clone(),
+                false => backoff,
+            };
+            match self.reconnect(&backoff).await {
+                Ok(Some(new_read)) => read = new_read,
+                Ok(None) => break Ok(()),
+                Err(error) => break Err(error),
+            }
+        };
+        self.shared.health.set_state(ConnectionState::Disconnected);
+
+        // Everything read is delivered before the reader finishes
+        drop(self.deliveries);
+        if let Err(error) = (&mut dispatcher.0).await {
+            error!("The dispatcher failed: {error}");
+        }
+        result
+    }
+
+    /// Queue `envelope` for the dispatcher, without waiting for the callback
+    fn deliver(&mut self, envelope: Envelope) {
+        if self.deliveries.send(envelope).is_err() {
+            error!("The dispatcher is gone, result dropped");
+        }
+    }
+
+    /// Handle the frames until the connection is lost
+    async fn read(&mut self, read: &mut Stream) -> Result<(), CryptoError> {
+        let mut join_result: Result<(), CryptoError> = Ok(());
+        let token = self.config.cancellation_token.clone();
+        let heartbeat_timeout = self.config.heartbeat_timeout;
+        let mut parse_failures = 0;
+        loop {
+            let next = tokio::select! {
+                next = next_frame(read, heartbeat_timeout) => next,
+                _ = cancelled(&token) => return self.shutdown(read).await,
+            };
+            let next = match next {
+                Ok(next) => next,
+                Err(timeout) => {
+                    error!("Nothing received in {timeout:?}");
+                    self.deliver(
+                        Received::now().envelope(Err(CryptoError::HeartbeatTimeout { timeout })),
+                    );
+                    return Err(CryptoError::HeartbeatTimeout { timeout });
+                }
+            };
+            let Some(next) = next else {
+                return join_result;
+            };
+            // Taken before anything else so the parsing time is measured too
+            let mut received = Received::now();
+            self.shared.health.frame_received(received.instant);
+            match next {
+                Ok(Message::Text(text)) => {
+                    debug!("Text received {text}");
+                    if self.config.raw_passthrough {
+                        received.raw = Some(raw_text(&text));
+                    }
+                    match message::parse(&text) {
+                        Ok(msg) => {
+                            parse_failures = 0;
+                            if let Err(error) = self.handle(received, msg).await {
+                                return self.close_with(error).await;
+                            }
+                        }
+                        Err(err) => {
+                            if let Some(notice) = message::maintenance_notice(&text) {
+                                self.announce_maintenance(&received, Some(notice));
+                                continue;
+                            }
+                            error!("Error when parsing JSON:\n{}\n{}", text, err);
+                            self.deliver(received.envelope(Err(CryptoError::SerdeError(err))));
+                            parse_failures += 1;
+                            if self
+                                .config
+                                .parse_failure_policy
+                                .limit()
+                                .is_some_and(|limit| parse_failures >= limit)
+                            {
+                                error!(
+                                    "Closing the connection after {parse_failures} frames that could not be parsed"
+                                );
+                                return self
+                                    .close_with(CryptoError::ParseFailures {
+                                        failures: parse_failures,
+                                    })
+                                    .await;
+                            }
+                        }
+                    }
+                }
+                Ok(Message::Ping(message)) => {
+                    debug!("Ping received {:?}", message);
+                    if let Err(error) = self.shared.send(Message::Pong(message)).await {
+                        error!("Cannot send pong");
+                        self.deliver(received.envelope(Err(error)));
+                    } else {
+                        debug!("Pong sent");
+                    }
+                }
+                Ok(Message::Pong(message)) => {
+                    debug!("PONG RECEIVED {:?}", message);
+                    self.shared.health.pong_received(received.instant);
+                }
+                Ok(Message::Close(frame)) => {
+                    if self.shared.closing.load(Ordering::SeqCst) {
+                        debug!("Close acknowledged");
+                        return join_result;
+                    }
+                    if let Some(resume_hint) = maintenance_close(frame.as_ref()) {
+                        self.announce_maintenance(&received, resume_hint.clone());
+                        return Err(CryptoError::Maintenance { resume_hint });
+                    }
+                    if self.maintenance {
+                        info!("Connection closed for the maintenance");
+                        return Err(CryptoError::CloseError { frame });
+                    }
+                    self.deliver(received.envelope(Err(CryptoError::CloseError {
+                        frame: frame.clone(),
+                    })));
+                    return Err(CryptoError::CloseError { frame });
+                }
+                Ok(message) => {
+                    error!("Unexpected message {:?}", message);
+                    self.deliver(
+                        received.envelope(Err(CryptoError::UnexpectedMessageError { message })),
+                    );
+                }
+                Err(error) if self.shared.closing.load(Ordering::SeqCst) => {
+                    debug!("Websocket read error while closing: {:?}", error);
+                    return join_result;
+                }
+                Err(error) => {
+                    error!("Websocket read error: {:?}", error);
+                    self.deliver(
+                        received
+                            .envelope(Err(CryptoError::TungsteniteErrorString(error.to_string()))),
+                    );
+                    join_result = Err(error.into());
+                }
+            }
+        }
+    }
+
+    /// Close the connection when the cancellation token is cancelled
+    async fn shutdown(&mut self, read: &mut Stream) -> Result<(), CryptoError> {
+        info!("Shutdown requested");
+        self.shared.closing.store(true, Ordering::SeqCst);
+        self.shared.forget_acks().await;
+        if let Some(mut writer) = self.shared.writer.lock().await.take() {
+            // Sends the close frame and flushes everything before it. If it cannot be sent
+            // the connection is going away anyway, so the answer is still waited for
+            if let Err(error) = writer.close().await {
+                debug!("Cannot send the close frame: {error}");
+            }
+        }
+
+        // Wait for the exchange to answer the close frame
+        let answered = async {
+            while let Some(Ok(message)) = read.next().await {
+                if message.is_close() {
+                    break;
+                }
+            }
+        };
+        if tokio::time::timeout(self.config.disconnect_timeout, answered)
+            .await
+            .is_err()
+        {
+            debug!("Close frame not answered");
+        }
+        Ok(())
+    }
+
+    /// Close the connection because of `error`, the reader reconnects unless closing
+    async fn close_with(&mut self, error: CryptoError) -> Result<(), CryptoError> {
+        if let Some(mut writer) = self.shared.writer.lock().await.take() {
+            if let Err(error) = writer.close().await {
+                debug!("Cannot send the close frame: {error}");
+            }
+        }
+        Err(error)
+    }
+
+    /// Fails only when the session cannot continue
+    async fn handle(
+        &mut self,
+        received: Received,
+        msg: message::Message,
+    ) -> Result<(), CryptoError> {
+        match msg {
+            message::Message::HeartbeatRequest { id } => {
+                debug!("heartbeat received");
+                self.shared.heartbeats.fetch_add(1, Ordering::SeqCst);
+                self.shared.health.heartbeat_received(received.instant);
+                match self.config.heartbeat_mode {
+                    HeartbeatMode::Auto => {
+                        let message = subscription::Request::HeartbeatResponse { id };
+                        match self.shared.send_request(&message).await {
+                            Ok(()) => {
+                                debug!("heartbeat sent");
+                                self.shared.health.heartbeat_answered();
+                            }
+                            Err(error) => {
+                                error!("Cannot send heartbeat");
+                                self.deliver(received.envelope(Err(error)));
+                            }
+                        }
+                    }
+                    HeartbeatMode::Manual => {
+                        self.deliver(
+                            received.envelope(Ok(SubscribeResult::HeartbeatRequest { id })),
+                        );
+                    }
+                    HeartbeatMode::Disabled => {
+                        debug!("heartbeat not answered");
+                    }
+                }
+            }
+            message::Message::SubscriptionResponse {
+                result,
+                id,
+                code,
+                channel,
+                message,
+            } => {
+                // The data frames name their channel in the result
+                let settled_channel = match &result {
+                    Some(result) => result.subscription(),
+                    None => channel.as_deref(),
+                };
+                let settled = match id {
+                    id if id >= 0 => {
+                        self.shared
+                            .acknowledge(id as u64, settled_channel, code)
+                            .await
+                    }
+                    _ => Settled::Unknown,
+                };
+                if code == 0 {
+                    // The session works, whatever happened before
+                    self.reauth_attempts = 0;
+                }
+                if let Some(result) = result {
+                    debug!("Message received: {:?}", result);
+                    self.deliver(received.envelope(Ok(result)));
+                } else if code != 0
+                    && message
+                        .as_deref()
+                        .is_some_and(message::mentions_maintenance)
+                {
+                    self.announce_maintenance(&received, message);
+                } else if code != 0 {
+                    match settled {
+                        Settled::Late => {
+                            info!("Ack of subscribe request {id} arrived after the timeout")
+                        }
+                        // Reported with the other channels of the request
+                        Settled::Pending => {}
+                        Settled::Done { channels, .. } if channels > 1 => {}
+                        _ => self.deliver(received.envelope(Err(CryptoError::SubscriptionError {
+                            id,
+                            code,
+                            message,
+                            channel,
+                        }))),
+                    }
+                }
+                if let Settled::Done {
+                    channels,
+                    accepted,
+                    rejected,
+                } = settled
+                {
+                    if !rejected.is_empty() {
+                        self.shared.forget_rejected(&rejected).await;
+                        if channels > 1 {
+                            self.deliver(received.envelope(Ok(
+                                SubscribeResult::PartialSubscription { accepted, rejected },
+                            )));
+                        }
+                    }
+                }
+                if UNAUTHORIZED_CODES.contains(&code) {
+                    self.reauthenticate().await?;
+                }
+            }
+            message::Message::UnsubscriptionResponse { id, code } => {
+                debug!("Unsubscription: {id} {code}");
+                // Only `disconnect` waits for these
+                if self.shared.pending.lock().await.contains_key(&(id as u64)) {
+                    self.respond(id as u64, code, None, Response::Unsubscription)
+                        .await?;
+                }
+                self.deliver(received.envelope(Ok(SubscribeResult::UnsubscriptionResult {
+                    success: code == 0,
+                })));
+            }
+            message::Message::InstrumentsResponse {
+                id,
+                code,
+                message,
+                result,
+            } => {
+                self.respond(id, code, message, Response::Instruments(result))
+                    .await?;
+            }
+            message::Message::BookResponse {
+                id,
+                code,
+                message,
+                result,
+            } => {
+                self.respond(id, code, message, Response::Book(result))
+                    .await?;
+            }
+            message::Message::AccountSummaryResponse {
+                id,
+                code,
+                message,
+                result,
+            } => {
+                self.respond(id, code, message, Response::AccountSummary(result))
+                    .await?;
+            }
+            message::Message::OpenOrdersResponse {
+                id,
+                code,
+                message,
+                result,
+            } => {
+                self.respond(id, code, message, Response::OpenOrders(result))
+                    .await?;
+            }
+            message::Message::OrderHistoryResponse {
+                id,
+                code,
+                message,
+                result,
+            } => {
+                self.respond(id, code, message, Response::OrderHistory(result))
+                    .await?;
+            }
+            message::Message::AuthResponse { id, code } => {
+                debug!("Notify auth response: {id} {code}");
+                self.shared.authenticated.store(code == 0, Ordering::SeqCst);
+                if code == 0 && self.resubscribe_on_auth {
+                    self.resubscribe_on_auth = false;
+                    self.resubscribe(false).await;
+                }
+                self.deliver(
+                    received.envelope(Ok(SubscribeResult::AuthResult { success: code == 0 })),
+                );
+                if self.reauthenticating {
+                    self.reauthenticating = false;
+                    if code == 0 {
+                        info!("Authenticated again");
+                        self.resubscribe(true).await;
+                        self.deliver(received.envelope(Ok(SubscribeResult::Reauthenticated {
+                            attempt: self.reauth_attempts,
+                        })));
+                    } else {
+                        self.reauthenticate().await?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Hand the response of request `id` to the task waiting for it
+    async fn respond(
+        &mut self,
+        id: u64,
+        code: u64,
+        message: Option<String>,
+        response: Response,
+    ) -> Result<(), CryptoError> {
+        let method = response.method();
+        match self.shared.pending.lock().await.remove(&id) {
+            Some(sender) => {
+                let result = if code == 0 {
+                    Ok(response)
+                } else {
+                    Err(CryptoError::RequestError {
+                        id,
+                        method: method.to_owned(),
+                        code,
+                        message,
+                    })
+                };
+                sender.send(result).ok();
+            }
+            None => debug!("Nobody is waiting for the response of {method} (msgid:{id})"),
+        }
+        if UNAUTHORIZED_CODES.contains(&code) {
+            self.reauthenticate().await?;
+        }
+        Ok(())
+    }
+
+    /// Open a new connection following `backoff` and restore the session on it
+    /// Returns `None` if the cancellation token is cancelled meanwhile
+    async fn reconnect(&mut self, backoff: &BackoffPolicy) -> Result<Option<Stream>, CryptoError> {
+        let token = self.config.cancellation_token.clone();
+        let mut attempt = 0;
+        loop {
+            attempt += 1;
+            self.shared.health.set_state(match self.maintenance {
+                true => ConnectionState::Maintenance,
+                false => ConnectionState::Connecting,
+            });
+            self.shared.health.set_reconnect_attempt(attempt);
+            let delay = backoff.delay(attempt);
+            info!("Reconnecting in {delay:?} (attempt {attempt})");
+            tokio::select! {
+                _ = tokio::time::sleep(delay) => {}
+                _ = cancelled(&token) => return Ok(None),
+            }
+            if self.shared.closing.load(Ordering::SeqCst) {
+                return Err(CryptoError::NotConnectedError);
+            }
+
+            match open(&self.uri, &self.config).await {
+                Ok((write, read)) => {
+                    {
+                        let mut writer = self.shared.writer.lock().await;
+                        if self.shared.closing.load(Ordering::SeqCst) {
+                            return Err(CryptoError::NotConnectedError);
+                        }
+                        *writer = Some(write);
+                    }
+                    self.shared.authenticated.store(false, Ordering::SeqCst);
+                    info!("Reconnected");
+                    self.shared.health.set_state(ConnectionState::Connected);
+                    self.shared.health.set_reconnect_attempt(0);
+                    self.restore().await;
+                    self.deliver(
+                        Received::now().envelope(Ok(SubscribeResult::Reconnected { attempt })),
+                    );
+                    if self.maintenance {
+                        info!("The maintenance is over");
+                        self.maintenance = false;
+                        self.deliver(
+                            Received::now()
+                                .envelope(Ok(SubscribeResult::MaintenanceEnded { attempt })),
+                        );
+                    }
+                    return Ok(Some(read));
+                }
+                Err(error) => {
+                    error!("Cannot reconnect: {error}");
+                    self.deliver(Received::now().envelope(Err(error)));
+                    if backoff.max_attempts.is_some_and(|max| attempt >= max) {
+                        let error = CryptoError::ReconnectFailed { attempts: attempt };
+                        self.deliver(
+                            Received::now()
+                                .envelope(Err(CryptoError::ReconnectFailed { attempts: attempt })),
+                        );
+                        return Err(error);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Deliver `CryptoError::Maintenance` the first time the exchange announces it, the
+    /// connection is expected to close soon
+    fn announce_maintenance(&mut self, received: &Received, resume_hint: Option<String>) {
+        if self.maintenance {
+            return;
+        }
+        info!("The exchange is under maintenance: {resume_hint:?}");
+        self.maintenance = true;
+        self.deliver(received.envelope(Err(CryptoError::Maintenance { resume_hint })));
+    }
+
+    /// Authenticate the new connection if there are credentials for it and
+    /// subscribe again to the channels of the previous one
+    async fn restore(&mut self) {
+        if self.credentials().is_none() {
+            self.resubscribe(false).await;
+            return;
+        }
+        if self.authenticate().await {
+            self.resubscribe_on_auth = true;
+        }
+    }
+
+    /// Credentials of the connection, only the user one has them
+    fn credentials(&self) -> Option<&Credentials> {
+        self.config
+            .credentials
+            .as_ref()
+            .filter(|_| self.uri == self.config.user_url)
+    }
+
+    /// Send the auth request, false if it cannot be sent
+    async fn authenticate(&mut self) -> bool {
+        let Some(credentials) = self.credentials().cloned() else {
+            return false;
+        };
+        let id = self.shared.next_id();
+        let result = match auth_request(id, &credentials.api_key, &credentials.api_secret) {
+            Ok(request) => self.shared.send_request(&request).await,
+            Err(error) => Err(error.into()),
+        };
+        match result {
+            Ok(()) => true,
+            Err(error) => {
+                error!("Cannot authenticate again");
+                self.deliver(Received::now().envelope(Err(error)));
+                false
+            }
+        }
+    }
+
+    /// The exchange does not consider the session authenticated anymore, authenticate
+    /// again and subscribe to the user channels once it succeeds
+    async fn reauthenticate(&mut self) -> Result<(), CryptoError> {
+        if self.reauthenticating || self.resubscribe_on_auth || self.credentials().is_none() {
+            return Ok(());
+        }
+        if self.reauth_attempts >= self.config.max_reauth_attempts {
+            let attempts = self.reauth_attempts;
+            error!("Cannot authenticate again after {attempts} attempts");
+            self.shared.closing.store(true, Ordering::SeqCst);
+            self.shared.forget_acks().await;
+            self.deliver(
+                Received::now().envelope(Err(CryptoError::AuthenticationFailed { attempts })),
+            );
+            return Err(CryptoError::AuthenticationFailed { attempts });
+        }
+        self.reauth_attempts += 1;
+        info!(
+            "Session not authenticated, authenticating again (attempt {})",
+            self.reauth_attempts
+        );
+        self.shared.authenticated.store(false, Ordering::SeqCst);
+        self.reauthenticating = self.authenticate().await;
+        Ok(())
+    }
+
+    /// Subscribe again to the channels of the registry, only to the user ones if `user_only`
+    async fn resubscribe(&mut self, user_only: bool) {
+        let channels: Vec<String> = self
+            .shared
+            .subscriptions
+            .lock()
+            .await
+            .iter()
+            .filter(|channel| !user_only || channel.starts_with("user."))
+            .cloned()
+            .collect();
+        if channels.is_empty() {
+            return;
+        }
+        debug!("Subscribing again to {} channels", channels.len());
+        let params = match serde_json::to_value(subscription::SubscribeParams {
+            channels: channels.clone(),
+        }) {
+            Ok(params) => params,
+            Err(error) => {
+                self.deliver(Received::now().envelope(Err(error.into())));
+                return;
+            }
+        };
+        let id = self.shared.next_id();
+        let request = subscription::Request::Subscribe {
+            id,
+            params,
+            nonce: nonce(),
+        };
+        let timer = spawn_ack_timer(
+            Arc::clone(&self.shared),
+            self.deliveries.downgrade(),
+            id,
+            self.config.ack_timeout,
+        );
+        self.shared.wait_ack(id, channels, timer).await;
+        if let Err(error) = self.shared.send_request(&request).await {
+            self.shared.cancel_ack(id).await;
+            error!("Cannot subscribe again");
+            self.deliver(Received::now().envelope(Err(error)));
+        }
+    }
+}
+
+/// Deliver `AckTimeout` with the channels of the subscribe request `id` not settled in
+/// `timeout`, after `PartialSubscription` if some were rejected meanwhile. They go through
+/// the dispatcher queue like the other results, nothing is delivered once the reader is gone
+pub(crate) fn spawn_ack_timer(
+    shared: Arc<Shared>,
+    deliveries: WeakUnboundedSender<Envelope>,
+    id: u64,
+    timeout: Duration,
+) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        tokio::time::sleep(timeout).await;
+        let Some(expired) = shared.expire_ack(id).await else {
+            return;
+        };
+        error!("Subscribe request {id} not acknowledged in {timeout:?}");
+        let Some(deliveries) = deliveries.upgrade() else {
+            return;
+        };
+        if !expired.rejected.is_empty() {
+            shared.forget_rejected(&expired.rejected).await;
+            deliveries
+                .send(
+                    Received::now().envelope(Ok(SubscribeResult::PartialSubscription {
+                        accepted: expired.accepted,
+                        rejected: expired.rejected,
+                    })),
+                )
+                .ok();
+        }
+        deliveries
+            .send(Received::now().envelope(Err(CryptoError::AckTimeout {
+                id,
+                channels: expired.unsettled,
+            })))
+            .ok();
+    })
+}
+
+/// Call the handler of the channel, or the callback, with every envelope of `queue`
+/// in order. The reader keeps answering heartbeats and pings while a callback is slow,
+/// the envelopes wait in the queue meanwhile.
+fn spawn_dispatcher<Fut, T>(
+    events: EventType<T, Fut>,
+    container: T,
+    shared: Arc<Shared>,
+    mut queue: UnboundedReceiver<Envelope>,
+) -> JoinHandle<()>
+where
+    Fut: std::future::Future<Output = ()> + Send + Sync + 'static,
+    T: Clone + Send + 'static,
+{
+    tokio::spawn(async move {
+        while let Some(mut envelope) = queue.recv().await {
+            if let Ok(SubscribeResult::TickerResult(result)) = &mut envelope.result {
+                if result.is_all_instruments() {
+                    for (handler, tickers) in shared.router.take_tickers(result) {
+                        handler(Envelope {
+                            received_at: envelope.received_at,
+                            received_instant: envelope.received_instant,
+                            result: Ok(SubscribeResult::TickerResult(tickers)),
+                            // The text of the whole frame, shared and not copied
+                            raw: envelope.raw.clone(),
+                        })
+                        .await;
+                    }
+                    if result.data.is_empty() {
+                        continue;
+                    }
+                }
+            }
+            let handler = match &envelope.result {
+                Ok(result) => result
+                    .subscription()
+                    .and_then(|subscription| shared.router.handler(subscription)),
+                Err(_) => None,
+            };
+            match handler {
+                Some(handler) => handler(envelope).await,
+                None => events(envelope, container.clone()).await,
+            }
+        }
+    })
+}
+
+/// Close codes the exchange uses when it goes down for a while: service restart and
+/// try again later
+const MAINTENANCE_CLOSE_CODES: [CloseCode; 2] = [CloseCode::Restart, CloseCode::Again];
+
+/// The reason of a close `frame` caused by a maintenance, if any
+fn maintenance_close(frame: Option<&CloseFrame>) -> Option<Option<String>> {
+    let frame = frame?;
+    let reason = frame.reason.as_ref();
+    if !MAINTENANCE_CLOSE_CODES.contains(&frame.code) && !message::mentions_maintenance(reason) {
+        return None;
+    }
+    Some((!reason.is_empty()).then(|| reason.to_owned()))
+}
+
+/// Next frame of `read`, the timeout as error if nothing arrives in time
+async fn next_frame(
+    read: &mut Stream,
+    timeout: Option<Duration>,
+) -> Result<Option<Result<Message, tungstenite::Error>>, Duration> {
+    match timeout {
+        Some(timeout) => tokio::time::timeout(timeout, read.next())
+            .await
+            .map_err(|_| timeout),
+        None => Ok(read.next().await),
+    }
+}
+
+/// Completes when `token` is cancelled, never without token
+async fn cancelled(token: &Option<CancellationToken>) {
+    match token {
+        Some(token) => token.cancelled().await,
+        None => std::future::pending().await,
+    }
+}
+
+/// Ping the exchange every `interval` on whatever connection is open
+fn spawn_ping(
+    shared: Arc<Shared>,
+    interval: Duration,
+    token: Option<CancellationToken>,
+) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        // The first tick completes immediately
+        ticker.tick().await;
+        loop {
+            tokio::select! {
+                _ = ticker.tick() => {}
+                _ = cancelled(&token) => return,
+            }
+            match shared.send(Message::Ping(Vec::new())).await {
+                Ok(()) => shared.health.ping_sent(Instant::now()),
+                Err(error) => debug!("Cannot send ping: {error}"),
+            }
+        }
+    })
+}
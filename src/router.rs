@@ -0,0 +1,187 @@
+use futures::future::{BoxFuture, Future, FutureExt};
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+
+use crate::message::Envelope;
+use crate::model::{Channel, TickerResult};
+
+/// Handler of the results of some channels, see `CryptoClient::on_channel`
+pub(crate) type Handler = Arc<dyn Fn(Envelope) -> BoxFuture<'static, ()> + Send + Sync>;
+
+pub(crate) fn handler<F, Fut>(f: F) -> Handler
+where
+    F: Fn(Envelope) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = ()> + Send + 'static,
+{
+    Arc::new(move |envelope| f(envelope).boxed())
+}
+
+/// Handlers registered by channel or by channel prefix, the results without handler
+/// go to the callback of the client
+#[derive(Default)]
+pub(crate) struct Router {
+    channels: RwLock<HashMap<Channel, Handler>>,
+    prefixes: RwLock<Vec<(String, Handler)>>,
+}
+
+impl Router {
+    /// Replaces the handler of `channel` if there was one
+    pub(crate) fn add_channel(&self, channel: Channel, handler: Handler) {
+        self.channels.write().unwrap().insert(channel, handler);
+    }
+
+    /// Replaces the handler of `prefix` if there was one
+    pub(crate) fn add_prefix(&self, prefix: String, handler: Handler) {
+        let mut prefixes = self.prefixes.write().unwrap();
+        prefixes.retain(|(registered, _)| *registered != prefix);
+        prefixes.push((prefix, handler));
+    }
+
+    /// Returns false if `channel` had no handler
+    pub(crate) fn remove_channel(&self, channel: &Channel) -> bool {
+        self.channels.write().unwrap().remove(channel).is_some()
+    }
+
+    /// Returns false if `prefix` had no handler
+    pub(crate) fn remove_prefix(&self, prefix: &str) -> bool {
+        let mut prefixes = self.prefixes.write().unwrap();
+        let before = prefixes.len();
+        prefixes.retain(|(registered, _)| registered != prefix);
+        prefixes.len() != before
+    }
+
+    /// Handler of the results of `subscription`: the one of its channel,
+    /// otherwise the one of the longest matching prefix
+    pub(crate) fn handler(&self, subscription: &str) -> Option<Handler> {
+        {
+            let channels = self.channels.read().unwrap();
+            if !channels.is_empty() {
+                let handler = subscription
+                    .parse::<Channel>()
+                    .ok()
+                    .and_then(|channel| channels.get(&channel).cloned());
+                if handler.is_some() {
+                    return handler;
+                }
+            }
+        }
+        self.prefixes
+            .read()
+            .unwrap()
+            .iter()
+            .filter(|(prefix, _)| subscription.starts_with(prefix.as_str()))
+            .max_by_key(|(prefix, _)| prefix.len())
+            .map(|(_, handler)| handler.clone())
+    }
+
+    /// Take out of a result of all the tickers the ones of the instruments with a ticker
+    /// handler, grouped by instrument as if they came from `ticker.{instrument_name}`
+    pub(crate) fn take_tickers(&self, result: &mut TickerResult) -> Vec<(Handler, TickerResult)> {
+        let channels = self.channels.read().unwrap();
+        if channels.is_empty() {
+            return Vec::new();
+        }
+        let mut routed: Vec<(Handler, TickerResult)> = Vec::new();
+        let mut rest = Vec::new();
+        for ticker in std::mem::take(&mut result.data) {
+            let instrument_name = ticker
+                .instrument_name
+                .clone()
+                .unwrap_or_else(|| result.instrument_name.clone());
+            let channel = Channel::Ticker {
+                instrument_name: instrument_name.clone(),
+            };
+            let Some(handler) = channels.get(&channel) else {
+                rest.push(ticker);
+                continue;
+            };
+            let subscription = channel.to_string();
+            match routed
+                .iter_mut()
+                .find(|(_, tickers)| tickers.subscription == subscription)
+            {
+                Some((_, tickers)) => tickers.data.push(ticker),
+                None => {
+                    routed.push((
+                        handler.clone(),
+                        TickerResult {
+                            instrument_name,
+                            subscription,
+                            data: vec![ticker],
+                        },
+                    ));
+                }
+            }
+        }
+        result.data = rest;
+        routed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn same(found: Option<Handler>, expected: &Handler) -> bool {
+        found.is_some_and(|found| Arc::ptr_eq(&found, expected))
+    }
+
+    #[test]
+    fn channel_before_longest_prefix() {
+        let router = Router::default();
+        let book = handler(|_| async {});
+        let user = handler(|_| async {});
+        let user_order = handler(|_| async {});
+        router.add_channel(
+            Channel::Book {
+                instrument_name: "ETH_CRO".to_owned(),
+                depth: 10,
+            },
+            book.clone(),
+        );
+        router.add_prefix("user.".to_owned(), user.clone());
+        router.add_prefix("user.order.".to_owned(), user_order.clone());
+        router.add_prefix("book.".to_owned(), user.clone());
+
+        assert!(same(router.handler("book.ETH_CRO.10"), &book));
+        assert!(same(router.handler("book.ETH_CRO.150"), &user));
+        assert!(same(router.handler("user.order.ETH_CRO"), &user_order));
+        assert!(same(router.handler("user.balance"), &user));
+        assert!(router.handler("trade.ETH_CRO").is_none());
+
+        assert!(router.remove_prefix("book."));
+        assert!(!router.remove_prefix("book."));
+        assert!(router.handler("book.ETH_CRO.150").is_none());
+        assert!(router.remove_channel(&"book.ETH_CRO.10".parse().unwrap()));
+        assert!(router.handler("book.ETH_CRO.10").is_none());
+    }
+
+    #[test]
+    fn tickers_are_taken_by_instrument() {
+        let router = Router::default();
+        let eth = handler(|_| async {});
+        router.add_channel(
+            Channel::Ticker {
+                instrument_name: "ETH_CRO".to_owned(),
+            },
+            eth.clone(),
+        );
+        let mut result: TickerResult = serde_json::from_str(
+            "{\"subscription\": \"ticker\", \"data\": [
+                {\"i\": \"BTC_USDT\", \"h\": 1, \"v\": 1, \"a\": 1, \"l\": 1, \"b\": 1, \"k\": 1, \"c\": 0, \"t\": 1},
+                {\"i\": \"ETH_CRO\", \"h\": 1, \"v\": 1, \"a\": 1, \"l\": 1, \"b\": 1, \"k\": 1, \"c\": 0, \"t\": 2},
+                {\"i\": \"ETH_CRO\", \"h\": 1, \"v\": 1, \"a\": 1, \"l\": 1, \"b\": 1, \"k\": 1, \"c\": 0, \"t\": 3}
+            ]}",
+        )
+        .unwrap();
+
+        let routed = router.take_tickers(&mut result);
+        assert_eq!(routed.len(), 1);
+        let (found, tickers) = &routed[0];
+        assert!(same(Some(found.clone()), &eth));
+        assert_eq!(tickers.instrument_name, "ETH_CRO");
+        assert_eq!(tickers.data.len(), 2);
+        assert_eq!(result.data.len(), 1);
+        assert_eq!(result.data[0].instrument_name.as_deref(), Some("BTC_USDT"));
+    }
+}
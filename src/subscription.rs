@@ -16,6 +16,43 @@ pub struct UnsubscribeParams {
     pub channels: Vec<String>
 }
 
+/// Parameters of a book request
+#[derive(Serialize, Debug)]
+pub struct GetBookParams {
+    /// The instrument, for example 'ETH_CRO'
+    pub instrument_name: String,
+    /// Number of bids and asks to return (up to 150)
+    pub depth: i32,
+}
+
+/// Parameters of an account summary request
+#[derive(Serialize, Debug)]
+pub struct AccountSummaryParams {
+    /// Only this currency, all of them if not present
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub currency: Option<String>,
+}
+
+/// Parameters of the order requests, the exchange defaults are used for missing ones
+#[derive(Serialize, Debug, Default)]
+pub struct OrderListParams {
+    /// Only orders of this instrument
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub instrument_name: Option<String>,
+    /// Start time in millis since epoch (history only)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub start_ts: Option<u64>,
+    /// End time in millis since epoch (history only)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub end_ts: Option<u64>,
+    /// Orders per page
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub page_size: Option<u32>,
+    /// Page number, starting at 0
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub page: Option<u32>,
+}
+
 /// A request done from the client to the exchange
 #[derive(Serialize, Debug)]
 #[serde(tag = "method")]
@@ -61,7 +98,60 @@ pub enum Request {
         /// Millis since epoc
         nonce: u128
     },
-    
+
+    /// Instruments request
+    #[serde(rename = "public/get-instruments")]
+    GetInstruments {
+        /// The exchange will response using this id, ideally it is unique
+        id: u64,
+        /// Millis since epoch
+        nonce: u128,
+    },
+
+    /// Book snapshot request
+    #[serde(rename = "public/get-book")]
+    GetBook {
+        /// The exchange will response using this id, ideally it is unique
+        id: u64,
+        /// The book to request
+        params: GetBookParams,
+        /// Millis since epoch
+        nonce: u128,
+    },
+
+    /// Account summary request, needs an authenticated connection
+    #[serde(rename = "private/get-account-summary")]
+    GetAccountSummary {
+        /// The exchange will response using this id, ideally it is unique
+        id: u64,
+        /// The currencies to request
+        params: AccountSummaryParams,
+        /// Millis since epoch
+        nonce: u128,
+    },
+
+    /// Open orders request, needs an authenticated connection
+    #[serde(rename = "private/get-open-orders")]
+    GetOpenOrders {
+        /// The exchange will response using this id, ideally it is unique
+        id: u64,
+        /// Filter and page
+        params: OrderListParams,
+        /// Millis since epoch
+        nonce: u128,
+    },
+
+    /// Order history request, needs an authenticated connection
+    #[serde(rename = "private/get-order-history")]
+    GetOrderHistory {
+        /// The exchange will response using this id, ideally it is unique
+        id: u64,
+        /// Filter and page
+        params: OrderListParams,
+        /// Millis since epoch
+        nonce: u128,
+    },
+
 }
 
 
@@ -83,6 +173,13 @@ mod tests {
         assert_eq!(text, "{\"method\":\"subscribe\",\"id\":22,\"params\":{\"channels\":[\"channel1\",\"channel2\"]},\"nonce\":18271187217812782}");
     }
 
+    #[test]
+    fn check_get_instruments_structure() {
+        let request = Request::GetInstruments{id: 3, nonce: 18271187217812782};
+        let text = to_string(&request).unwrap();
+        assert_eq!(text, "{\"method\":\"public/get-instruments\",\"id\":3,\"nonce\":18271187217812782}");
+    }
+
     #[test]
     fn check_heartbeat_structure() {
         let hb = Request::HeartbeatResponse{id: 19};